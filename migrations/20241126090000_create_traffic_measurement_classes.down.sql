@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS traffic_measurement_classes;
+DROP TYPE IF EXISTS vehicle_class;
@@ -0,0 +1,29 @@
+-- Create enum for the MIV vehicle classes (klasse_id)
+CREATE TYPE vehicle_class AS ENUM (
+    'MOTOR_BIKES',
+    'CARS',
+    'VANS',
+    'RIGID_TRUCKS',
+    'ARTICULATED_TRUCKS',
+    'UNKNOWN'
+);
+
+-- Create table for the per vehicle class measurements
+CREATE TABLE traffic_measurement_classes (
+    location_id INTEGER NOT NULL,
+    observation_time TIMESTAMPTZ NOT NULL,
+    vehicle_class vehicle_class NOT NULL,
+
+    traffic_intensity INTEGER NOT NULL,
+    vehicle_speed_arithmetic INTEGER NOT NULL,
+    vehicle_speed_harmonic INTEGER NOT NULL,
+
+    PRIMARY KEY (location_id, observation_time, vehicle_class)
+);
+
+-- Create hypertable for the per vehicle class data
+SELECT create_hypertable('traffic_measurement_classes', 'observation_time');
+
+-- Create indexes for common queries
+CREATE INDEX idx_traffic_measurement_classes_location
+    ON traffic_measurement_classes (location_id, observation_time);
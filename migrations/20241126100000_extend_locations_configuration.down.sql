@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_locations_equipment_number;
+
+ALTER TABLE locations
+    DROP COLUMN IF EXISTS descriptive_id,
+    DROP COLUMN IF EXISTS full_name,
+    DROP COLUMN IF EXISTS ident_8,
+    DROP COLUMN IF EXISTS equipment_number,
+    DROP COLUMN IF EXISTS kilometer_point,
+    DROP COLUMN IF EXISTS lane,
+    DROP COLUMN IF EXISTS lambert_x,
+    DROP COLUMN IF EXISTS lambert_y;
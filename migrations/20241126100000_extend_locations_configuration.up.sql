@@ -0,0 +1,23 @@
+-- Store the full MIV location configuration. Existing rows get placeholder
+-- values which are overwritten by the next configuration import.
+ALTER TABLE locations
+    ADD COLUMN descriptive_id TEXT NOT NULL DEFAULT '',
+    ADD COLUMN full_name TEXT NOT NULL DEFAULT '',
+    ADD COLUMN ident_8 TEXT NOT NULL DEFAULT '',
+    ADD COLUMN equipment_number INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN kilometer_point DOUBLE PRECISION,
+    ADD COLUMN lane TEXT NOT NULL DEFAULT '',
+    ADD COLUMN lambert_x DOUBLE PRECISION NOT NULL DEFAULT 0,
+    ADD COLUMN lambert_y DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+ALTER TABLE locations
+    ALTER COLUMN descriptive_id DROP DEFAULT,
+    ALTER COLUMN full_name DROP DEFAULT,
+    ALTER COLUMN ident_8 DROP DEFAULT,
+    ALTER COLUMN equipment_number DROP DEFAULT,
+    ALTER COLUMN lane DROP DEFAULT,
+    ALTER COLUMN lambert_x DROP DEFAULT,
+    ALTER COLUMN lambert_y DROP DEFAULT;
+
+CREATE INDEX idx_locations_equipment_number
+    ON locations (equipment_number);
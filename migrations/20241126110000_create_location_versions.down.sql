@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS location_versions;
+DROP TABLE IF EXISTS location_configuration_versions;
@@ -0,0 +1,68 @@
+-- Create table for the imported MIV configuration versions (tijd_laatste_config_wijziging)
+CREATE TABLE location_configuration_versions (
+    config_time TIMESTAMPTZ PRIMARY KEY,
+    imported_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    location_count INTEGER NOT NULL
+);
+
+-- Create table for the location configuration history. Every row is valid in
+-- the range [valid_from, valid_to), an open ended row is the current configuration.
+CREATE TABLE location_versions (
+    location_id INTEGER NOT NULL,
+    descriptive_id TEXT NOT NULL,
+    full_name TEXT NOT NULL,
+    ident_8 TEXT NOT NULL,
+    equipment_number INTEGER NOT NULL,
+    kilometer_point DOUBLE PRECISION,
+    lane TEXT NOT NULL,
+    lambert_x DOUBLE PRECISION NOT NULL,
+    lambert_y DOUBLE PRECISION NOT NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+
+    valid_from TIMESTAMPTZ NOT NULL,
+    valid_to TIMESTAMPTZ,
+
+    PRIMARY KEY (location_id, valid_from),
+    CHECK (valid_to IS NULL OR valid_to > valid_from)
+);
+
+-- Create indexes for common queries
+CREATE INDEX idx_location_versions_validity
+    ON location_versions (location_id, valid_from, valid_to);
+
+CREATE UNIQUE INDEX uniq_idx_location_versions_current
+    ON location_versions (location_id)
+    WHERE valid_to IS NULL;
+
+-- Seed the history with the locations we already know. Their original
+-- configuration time is unknown, so they are valid since the epoch. A finite
+-- sentinel instead of -infinity keeps the column decodable as a timestamp.
+INSERT INTO location_versions (
+    location_id,
+    descriptive_id,
+    full_name,
+    ident_8,
+    equipment_number,
+    kilometer_point,
+    lane,
+    lambert_x,
+    lambert_y,
+    latitude,
+    longitude,
+    valid_from
+)
+SELECT
+    location_id,
+    descriptive_id,
+    full_name,
+    ident_8,
+    equipment_number,
+    kilometer_point,
+    lane,
+    lambert_x,
+    lambert_y,
+    latitude,
+    longitude,
+    '1970-01-01 00:00:00+00'::TIMESTAMPTZ
+FROM locations;
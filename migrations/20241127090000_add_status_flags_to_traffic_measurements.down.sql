@@ -0,0 +1,5 @@
+ALTER TABLE traffic_measurements
+    DROP COLUMN IF EXISTS current_publication,
+    DROP COLUMN IF EXISTS available,
+    DROP COLUMN IF EXISTS faulty,
+    DROP COLUMN IF EXISTS valid;
@@ -0,0 +1,7 @@
+-- Store the sensor status flags of every measurement. Rows ingested before
+-- these columns existed have unknown (NULL) flags.
+ALTER TABLE traffic_measurements
+    ADD COLUMN current_publication BOOLEAN,
+    ADD COLUMN available BOOLEAN,
+    ADD COLUMN faulty BOOLEAN,
+    ADD COLUMN valid BOOLEAN;
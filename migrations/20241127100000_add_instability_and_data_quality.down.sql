@@ -0,0 +1,22 @@
+UPDATE traffic_measurement_classes
+    SET vehicle_speed_arithmetic = CASE data_quality
+            WHEN 'SPEED_UNKNOWN' THEN 251
+            WHEN 'NO_VEHICLES' THEN 252
+            WHEN 'SENSOR_ERROR' THEN 254
+        END
+    WHERE data_quality <> 'VALID';
+
+UPDATE traffic_measurement_classes
+    SET vehicle_speed_harmonic = vehicle_speed_arithmetic
+    WHERE vehicle_speed_harmonic IS NULL;
+
+ALTER TABLE traffic_measurement_classes
+    DROP COLUMN IF EXISTS data_quality,
+    ALTER COLUMN vehicle_speed_arithmetic SET NOT NULL,
+    ALTER COLUMN vehicle_speed_harmonic SET NOT NULL;
+
+ALTER TABLE traffic_measurements
+    DROP COLUMN IF EXISTS instability,
+    DROP COLUMN IF EXISTS data_quality;
+
+DROP TYPE IF EXISTS data_quality;
@@ -0,0 +1,41 @@
+-- Create enum for the data quality of a speed reading. The MIV feed encodes
+-- missing speeds with special values (251, 252, 254) instead of leaving them out.
+CREATE TYPE data_quality AS ENUM (
+    'VALID',
+    'NO_VEHICLES',
+    'SPEED_UNKNOWN',
+    'SENSOR_ERROR'
+);
+
+-- Store the instability (onrustigheid) and the data quality of the aggregated reading
+ALTER TABLE traffic_measurements
+    ADD COLUMN instability INTEGER,
+    ADD COLUMN data_quality data_quality;
+
+-- Rows stored before only kept a speed when one of the classes had a valid speed
+UPDATE traffic_measurements
+    SET data_quality = 'VALID'
+    WHERE average_speed IS NOT NULL;
+
+-- Store the data quality per vehicle class reading, speeds are NULL unless valid
+ALTER TABLE traffic_measurement_classes
+    ADD COLUMN data_quality data_quality NOT NULL DEFAULT 'VALID',
+    ALTER COLUMN vehicle_speed_arithmetic DROP NOT NULL,
+    ALTER COLUMN vehicle_speed_harmonic DROP NOT NULL;
+
+UPDATE traffic_measurement_classes
+    SET data_quality = CASE vehicle_speed_arithmetic
+            WHEN 251 THEN 'SPEED_UNKNOWN'::data_quality
+            WHEN 252 THEN 'NO_VEHICLES'::data_quality
+            WHEN 254 THEN 'SENSOR_ERROR'::data_quality
+        END,
+        vehicle_speed_arithmetic = NULL,
+        vehicle_speed_harmonic = NULL
+    WHERE vehicle_speed_arithmetic IN (251, 252, 254);
+
+UPDATE traffic_measurement_classes
+    SET vehicle_speed_harmonic = NULL
+    WHERE vehicle_speed_harmonic IN (251, 252, 254);
+
+ALTER TABLE traffic_measurement_classes
+    ALTER COLUMN data_quality DROP DEFAULT;
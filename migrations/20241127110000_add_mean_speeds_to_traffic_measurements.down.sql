@@ -0,0 +1,3 @@
+ALTER TABLE traffic_measurements
+    DROP COLUMN IF EXISTS time_mean_speed,
+    DROP COLUMN IF EXISTS space_mean_speed;
@@ -0,0 +1,4 @@
+-- Store the intensity-weighted time-mean speed and the harmonic space-mean speed
+ALTER TABLE traffic_measurements
+    ADD COLUMN time_mean_speed DOUBLE PRECISION,
+    ADD COLUMN space_mean_speed DOUBLE PRECISION;
@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS idx_location_versions_geog;
+DROP INDEX IF EXISTS idx_locations_geog;
+
+ALTER TABLE location_versions
+    DROP COLUMN IF EXISTS geog;
+
+ALTER TABLE locations
+    DROP COLUMN IF EXISTS geog;
+
+CREATE INDEX idx_locations_location
+    ON locations USING GIST (
+        ST_SetSRID(ST_MakePoint(longitude, latitude), 4326)
+    );
@@ -0,0 +1,17 @@
+-- Store the location as geography so distances and radius searches are in meters
+ALTER TABLE locations
+    ADD COLUMN geog geography(Point, 4326)
+    GENERATED ALWAYS AS (ST_SetSRID(ST_MakePoint(longitude, latitude), 4326)::geography) STORED;
+
+ALTER TABLE location_versions
+    ADD COLUMN geog geography(Point, 4326)
+    GENERATED ALWAYS AS (ST_SetSRID(ST_MakePoint(longitude, latitude), 4326)::geography) STORED;
+
+-- Replace the expression index on the degree based geometry
+DROP INDEX IF EXISTS idx_locations_location;
+
+CREATE INDEX idx_locations_geog
+    ON locations USING GIST (geog);
+
+CREATE INDEX idx_location_versions_geog
+    ON location_versions USING GIST (geog);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS current_traffic_measurements;
@@ -0,0 +1,43 @@
+-- Create table with the latest measurement per location, kept up to date by
+-- the ingestion job so the live network state does not scan the hypertable.
+CREATE TABLE current_traffic_measurements (
+    location_id INTEGER PRIMARY KEY,
+    observation_time TIMESTAMPTZ NOT NULL,
+
+    availability_rate INTEGER NOT NULL,
+    occupancy_rate INTEGER,
+    instability INTEGER,
+    total_vehicles_passed INTEGER,
+    average_speed INTEGER,
+    max_speed INTEGER,
+    time_mean_speed DOUBLE PRECISION,
+    space_mean_speed DOUBLE PRECISION,
+    data_quality data_quality,
+
+    current_publication BOOLEAN,
+    available BOOLEAN,
+    faulty BOOLEAN,
+    valid BOOLEAN
+);
+
+-- Seed it with the most recent measurements we already have
+INSERT INTO current_traffic_measurements
+SELECT DISTINCT ON (location_id)
+    location_id,
+    observation_time,
+    availability_rate,
+    occupancy_rate,
+    instability,
+    total_vehicles_passed,
+    average_speed,
+    max_speed,
+    time_mean_speed,
+    space_mean_speed,
+    data_quality,
+    current_publication,
+    available,
+    faulty,
+    valid
+FROM traffic_measurements
+WHERE observation_time > NOW() - INTERVAL '1 day'
+ORDER BY location_id, observation_time DESC;
@@ -0,0 +1,9 @@
+DROP FUNCTION IF EXISTS is_valid_measurement(BOOLEAN, BOOLEAN, BOOLEAN, plausibility_flag[]);
+
+ALTER TABLE current_traffic_measurements
+    DROP COLUMN IF EXISTS plausibility_flags;
+
+ALTER TABLE traffic_measurements
+    DROP COLUMN IF EXISTS plausibility_flags;
+
+DROP TYPE IF EXISTS plausibility_flag;
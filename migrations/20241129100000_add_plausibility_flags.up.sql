@@ -0,0 +1,34 @@
+-- Reason codes of the plausibility checks run by the ingestion job
+CREATE TYPE plausibility_flag AS ENUM (
+    -- Identical readings for a long stretch of time
+    'STUCK_VALUE',
+    -- Identical readings as another lane of the same measuring station
+    'DUPLICATE_LANE',
+    -- Speed above what a vehicle class average can physically reach
+    'SPEED_ABOVE_LIMIT',
+    -- Occupancy that does not fit the intensity and speed
+    'OCCUPANCY_MISMATCH'
+);
+
+-- NULL for rows that were never checked, empty when every check passed
+ALTER TABLE traffic_measurements
+    ADD COLUMN plausibility_flags plausibility_flag[];
+
+ALTER TABLE current_traffic_measurements
+    ADD COLUMN plausibility_flags plausibility_flag[];
+
+-- A single definition of a usable reading, shared by the queries and the aggregates:
+-- the sensor is available, not faulty, not marked invalid and the reading passed
+-- the plausibility checks (or predates them)
+CREATE FUNCTION is_valid_measurement(
+    valid BOOLEAN,
+    available BOOLEAN,
+    faulty BOOLEAN,
+    plausibility_flags plausibility_flag[]
+) RETURNS BOOLEAN
+LANGUAGE SQL IMMUTABLE AS $$
+    SELECT valid IS NOT FALSE
+        AND available IS NOT FALSE
+        AND faulty IS NOT TRUE
+        AND (plausibility_flags IS NULL OR cardinality(plausibility_flags) = 0)
+$$;
@@ -0,0 +1,3 @@
+DROP MATERIALIZED VIEW IF EXISTS traffic_measurements_daily;
+DROP MATERIALIZED VIEW IF EXISTS traffic_measurements_hourly;
+DROP MATERIALIZED VIEW IF EXISTS traffic_measurements_15m;
@@ -0,0 +1,81 @@
+-- Continuous aggregates per location at 15 minute, hourly and daily granularity.
+-- They store sums so coarser aggregates can be built on top of finer ones and
+-- weighted speeds stay correct; averages are derived when querying.
+-- Readings of unavailable, faulty or invalid sensors and readings flagged by the
+-- plausibility checks are left out. The ingestion job flags readings right after
+-- inserting them, before the refresh policies reach their buckets.
+-- Created without data, the existing history is materialized by the backfill at startup.
+CREATE MATERIALIZED VIEW traffic_measurements_15m
+WITH (timescaledb.continuous) AS
+SELECT
+    location_id,
+    time_bucket(INTERVAL '15 minutes', observation_time) AS bucket,
+    SUM(total_vehicles_passed)::BIGINT AS total_vehicles_passed,
+    (SUM(total_vehicles_passed) FILTER (WHERE time_mean_speed IS NOT NULL))::BIGINT AS time_mean_vehicles,
+    SUM(total_vehicles_passed * time_mean_speed) AS time_mean_speed_sum,
+    (SUM(total_vehicles_passed) FILTER (WHERE space_mean_speed > 0))::BIGINT AS space_mean_vehicles,
+    SUM(total_vehicles_passed / space_mean_speed) FILTER (WHERE space_mean_speed > 0) AS inverse_speed_sum,
+    SUM(occupancy_rate)::BIGINT AS occupancy_rate_sum,
+    COUNT(occupancy_rate) AS occupancy_rate_count,
+    COUNT(*) AS sample_count
+FROM traffic_measurements
+WHERE is_valid_measurement(valid, available, faulty, plausibility_flags)
+GROUP BY location_id, bucket
+WITH NO DATA;
+
+CREATE MATERIALIZED VIEW traffic_measurements_hourly
+WITH (timescaledb.continuous) AS
+SELECT
+    location_id,
+    time_bucket(INTERVAL '1 hour', bucket) AS bucket,
+    SUM(total_vehicles_passed)::BIGINT AS total_vehicles_passed,
+    SUM(time_mean_vehicles)::BIGINT AS time_mean_vehicles,
+    SUM(time_mean_speed_sum) AS time_mean_speed_sum,
+    SUM(space_mean_vehicles)::BIGINT AS space_mean_vehicles,
+    SUM(inverse_speed_sum) AS inverse_speed_sum,
+    SUM(occupancy_rate_sum)::BIGINT AS occupancy_rate_sum,
+    SUM(occupancy_rate_count)::BIGINT AS occupancy_rate_count,
+    SUM(sample_count)::BIGINT AS sample_count
+FROM traffic_measurements_15m
+GROUP BY location_id, time_bucket(INTERVAL '1 hour', bucket)
+WITH NO DATA;
+
+CREATE MATERIALIZED VIEW traffic_measurements_daily
+WITH (timescaledb.continuous) AS
+SELECT
+    location_id,
+    time_bucket(INTERVAL '1 day', bucket) AS bucket,
+    SUM(total_vehicles_passed)::BIGINT AS total_vehicles_passed,
+    SUM(time_mean_vehicles)::BIGINT AS time_mean_vehicles,
+    SUM(time_mean_speed_sum) AS time_mean_speed_sum,
+    SUM(space_mean_vehicles)::BIGINT AS space_mean_vehicles,
+    SUM(inverse_speed_sum) AS inverse_speed_sum,
+    SUM(occupancy_rate_sum)::BIGINT AS occupancy_rate_sum,
+    SUM(occupancy_rate_count)::BIGINT AS occupancy_rate_count,
+    SUM(sample_count)::BIGINT AS sample_count
+FROM traffic_measurements_hourly
+GROUP BY location_id, time_bucket(INTERVAL '1 day', bucket)
+WITH NO DATA;
+
+CREATE INDEX idx_traffic_measurements_15m_location
+    ON traffic_measurements_15m (location_id, bucket);
+CREATE INDEX idx_traffic_measurements_hourly_location
+    ON traffic_measurements_hourly (location_id, bucket);
+CREATE INDEX idx_traffic_measurements_daily_location
+    ON traffic_measurements_daily (location_id, bucket);
+
+-- Refresh policies, each level only refreshes closed buckets
+SELECT add_continuous_aggregate_policy('traffic_measurements_15m',
+    start_offset => INTERVAL '1 day',
+    end_offset => INTERVAL '15 minutes',
+    schedule_interval => INTERVAL '15 minutes');
+
+SELECT add_continuous_aggregate_policy('traffic_measurements_hourly',
+    start_offset => INTERVAL '3 days',
+    end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '1 hour');
+
+SELECT add_continuous_aggregate_policy('traffic_measurements_daily',
+    start_offset => INTERVAL '7 days',
+    end_offset => INTERVAL '1 day',
+    schedule_interval => INTERVAL '1 day');
@@ -0,0 +1,14 @@
+SELECT remove_retention_policy('traffic_measurements_hourly', if_exists => true);
+SELECT remove_retention_policy('traffic_measurements_15m', if_exists => true);
+SELECT remove_retention_policy('traffic_measurement_classes', if_exists => true);
+SELECT remove_retention_policy('traffic_measurements', if_exists => true);
+
+SELECT remove_compression_policy('traffic_measurement_classes', if_exists => true);
+SELECT remove_compression_policy('traffic_measurements', if_exists => true);
+
+-- Decompress remaining chunks before compression can be turned off
+SELECT decompress_chunk(c, true) FROM show_chunks('traffic_measurement_classes') c;
+SELECT decompress_chunk(c, true) FROM show_chunks('traffic_measurements') c;
+
+ALTER TABLE traffic_measurement_classes SET (timescaledb.compress = false);
+ALTER TABLE traffic_measurements SET (timescaledb.compress = false);
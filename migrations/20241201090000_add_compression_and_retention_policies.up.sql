@@ -0,0 +1,25 @@
+-- Compress raw chunks per location, most recent observations first
+ALTER TABLE traffic_measurements SET (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'location_id',
+    timescaledb.compress_orderby = 'observation_time DESC'
+);
+
+ALTER TABLE traffic_measurement_classes SET (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'location_id',
+    timescaledb.compress_orderby = 'observation_time DESC, vehicle_class'
+);
+
+-- Default policies, the application overrides these from its configuration on startup.
+-- Raw data is kept well past the continuous aggregate refresh windows so the
+-- aggregates are never refreshed over dropped chunks.
+SELECT add_compression_policy('traffic_measurements', compress_after => INTERVAL '7 days');
+SELECT add_compression_policy('traffic_measurement_classes', compress_after => INTERVAL '7 days');
+
+SELECT add_retention_policy('traffic_measurements', drop_after => INTERVAL '90 days');
+SELECT add_retention_policy('traffic_measurement_classes', drop_after => INTERVAL '90 days');
+
+-- Aggregates outlive the raw data, the daily aggregate is kept indefinitely
+SELECT add_retention_policy('traffic_measurements_15m', drop_after => INTERVAL '1 year');
+SELECT add_retention_policy('traffic_measurements_hourly', drop_after => INTERVAL '3 years');
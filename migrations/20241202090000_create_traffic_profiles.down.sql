@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS traffic_profile_refreshes;
+DROP TABLE IF EXISTS traffic_profiles;
@@ -0,0 +1,38 @@
+-- Typical traffic per location, weekday and 15 minute slot of the (local) day,
+-- rebuilt from the 15 minute aggregate by the profile job
+CREATE TABLE traffic_profiles (
+    location_id INTEGER NOT NULL,
+    -- ISO weekday, 1 is monday
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    slot_start TIME NOT NULL,
+
+    -- Number of days that contributed to the slot
+    sample_count INTEGER NOT NULL,
+
+    -- Vehicles per 15 minutes
+    average_intensity DOUBLE PRECISION,
+    intensity_p10 DOUBLE PRECISION,
+    intensity_p50 DOUBLE PRECISION,
+    intensity_p90 DOUBLE PRECISION,
+
+    average_speed DOUBLE PRECISION,
+    speed_p10 DOUBLE PRECISION,
+    speed_p50 DOUBLE PRECISION,
+    speed_p90 DOUBLE PRECISION,
+
+    average_occupancy DOUBLE PRECISION,
+    occupancy_p10 DOUBLE PRECISION,
+    occupancy_p50 DOUBLE PRECISION,
+    occupancy_p90 DOUBLE PRECISION,
+
+    PRIMARY KEY (location_id, weekday, slot_start)
+);
+
+-- One row per profile rebuild, the latest one describes the current profiles
+CREATE TABLE traffic_profile_refreshes (
+    refreshed_at TIMESTAMPTZ PRIMARY KEY DEFAULT NOW(),
+    window_start TIMESTAMPTZ NOT NULL,
+    window_end TIMESTAMPTZ NOT NULL,
+    time_zone TEXT NOT NULL,
+    profile_count INTEGER NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP FUNCTION IF EXISTS classify_congestion(DOUBLE PRECISION, INTEGER, INTEGER, DOUBLE PRECISION);
+DROP TABLE IF EXISTS free_flow_speeds;
+DROP TYPE IF EXISTS congestion_level;
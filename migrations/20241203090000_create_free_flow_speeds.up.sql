@@ -0,0 +1,37 @@
+-- Level of service of a reading, ordered from least to most congested
+CREATE TYPE congestion_level AS ENUM (
+    'FREE_FLOW',
+    'DENSE',
+    'SLOW',
+    'STATIONARY'
+);
+
+-- Free-flow speed per location, learned from night-time readings with little traffic
+CREATE TABLE free_flow_speeds (
+    location_id INTEGER PRIMARY KEY,
+    free_flow_speed DOUBLE PRECISION NOT NULL,
+    -- Number of 15 minute buckets the baseline was learned from
+    sample_count INTEGER NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Classifies a reading against the free-flow speed of its location. Without a
+-- speed the reading is either an empty road or a standing queue over the sensor.
+CREATE FUNCTION classify_congestion(
+    speed DOUBLE PRECISION,
+    occupancy_rate INTEGER,
+    total_vehicles_passed INTEGER,
+    free_flow_speed DOUBLE PRECISION
+) RETURNS congestion_level
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT CASE
+        WHEN free_flow_speed IS NULL OR free_flow_speed <= 0 THEN NULL
+        WHEN speed IS NULL AND occupancy_rate >= 50 THEN 'STATIONARY'
+        WHEN speed IS NULL AND total_vehicles_passed = 0 THEN 'FREE_FLOW'
+        WHEN speed IS NULL THEN NULL
+        WHEN speed < 0.25 * free_flow_speed THEN 'STATIONARY'
+        WHEN speed < 0.5 * free_flow_speed THEN 'SLOW'
+        WHEN speed < 0.8 * free_flow_speed OR occupancy_rate >= 25 THEN 'DENSE'
+        ELSE 'FREE_FLOW'
+    END::congestion_level
+$$;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS traffic_anomalies;
+DROP TYPE IF EXISTS anomaly_metric;
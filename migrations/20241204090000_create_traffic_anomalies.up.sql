@@ -0,0 +1,40 @@
+-- Measured quantities an anomaly can be detected on
+CREATE TYPE anomaly_metric AS ENUM (
+    'INTENSITY',
+    'SPEED',
+    'OCCUPANCY'
+);
+
+-- Periods in which the readings of a location deviate significantly from its
+-- typical-day profile, extended by every ingestion run that still sees them
+CREATE TABLE traffic_anomalies (
+    location_id INTEGER NOT NULL,
+    metric anomaly_metric NOT NULL,
+
+    -- First and last deviating reading
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    -- Last deviating reading once the readings are back to normal, NULL while it lasts
+    ended_at TIMESTAMPTZ,
+
+    -- Of the most severe reading, over the valid readings of the 15 minutes up to
+    -- it, with the intensity in vehicles per 15 minutes like the profiles
+    observed_value DOUBLE PRECISION NOT NULL,
+    -- Median of the profile slot and its 10th/90th percentile band
+    expected_value DOUBLE PRECISION NOT NULL,
+    lower_bound DOUBLE PRECISION,
+    upper_bound DOUBLE PRECISION,
+    -- Deviation from the expected value in (robust) standard deviations
+    severity DOUBLE PRECISION NOT NULL,
+
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (location_id, started_at, metric)
+);
+
+CREATE UNIQUE INDEX idx_traffic_anomalies_open
+    ON traffic_anomalies (location_id, metric)
+    WHERE ended_at IS NULL;
+
+CREATE INDEX idx_traffic_anomalies_started_at
+    ON traffic_anomalies (started_at DESC);
@@ -0,0 +1,2 @@
+DROP VIEW IF EXISTS measuring_sites;
+DROP TYPE IF EXISTS road_direction;
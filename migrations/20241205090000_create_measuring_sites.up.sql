@@ -0,0 +1,31 @@
+-- Direction of travel relative to the kilometre points of the road
+CREATE TYPE road_direction AS ENUM (
+    'POSITIVE',
+    'NEGATIVE'
+);
+
+-- Measuring sites: the lanes (meetpunten) of one carriageway measured by the same
+-- equipment (lve_nr). Ident_8 is the road category, the 3 digit road number and a
+-- 4 digit carriageway code, ending in an odd digit for the direction of increasing
+-- kilometre points. 0001 and 0002 are the main carriageways, others are ramps.
+CREATE VIEW measuring_sites AS
+SELECT
+    ident_8 || '-' || equipment_number AS site_id,
+    equipment_number,
+    ident_8,
+    CASE WHEN ident_8 ~ '^[A-Z][0-9]{3}[0-9A-Za-z]{3}[0-9]$'
+        THEN substr(ident_8, 1, 1) || substr(ident_8, 2, 3)::INTEGER
+    END AS road,
+    CASE WHEN ident_8 ~ '^[A-Z][0-9]{3}[0-9A-Za-z]{3}[0-9]$'
+        THEN CASE WHEN substr(ident_8, 8, 1)::INTEGER % 2 = 1 THEN 'POSITIVE' ELSE 'NEGATIVE' END::road_direction
+    END AS direction,
+    ident_8 ~ '^[A-Z][0-9]{3}000[12]$' AS main_carriageway,
+    MIN(full_name) AS full_name,
+    AVG(kilometer_point) AS kilometer_point,
+    AVG(latitude) AS latitude,
+    AVG(longitude) AS longitude,
+    COUNT(*)::INTEGER AS lane_count,
+    array_agg(location_id ORDER BY lane) AS location_ids,
+    array_agg(lane ORDER BY lane) AS lanes
+FROM locations
+GROUP BY equipment_number, ident_8;
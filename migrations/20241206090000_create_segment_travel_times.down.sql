@@ -0,0 +1,3 @@
+SELECT remove_retention_policy('segment_travel_times', if_exists => true);
+
+DROP TABLE IF EXISTS segment_travel_times;
@@ -0,0 +1,27 @@
+-- Travel time over the stretch between two consecutive main carriageway sites of
+-- a road, computed by the ingestion job for every observed minute
+CREATE TABLE segment_travel_times (
+    from_site_id TEXT NOT NULL,
+    to_site_id TEXT NOT NULL,
+    observation_time TIMESTAMPTZ NOT NULL,
+
+    road TEXT NOT NULL,
+    direction road_direction NOT NULL,
+    from_kilometer_point DOUBLE PRECISION NOT NULL,
+    to_kilometer_point DOUBLE PRECISION NOT NULL,
+    length_km DOUBLE PRECISION NOT NULL,
+
+    -- Speeds used at both ends, the free-flow speed when a site saw no vehicles
+    from_speed DOUBLE PRECISION NOT NULL,
+    to_speed DOUBLE PRECISION NOT NULL,
+    travel_time_seconds DOUBLE PRECISION NOT NULL,
+
+    PRIMARY KEY (from_site_id, to_site_id, observation_time)
+);
+
+SELECT create_hypertable('segment_travel_times', 'observation_time');
+
+CREATE INDEX idx_segment_travel_times_road
+    ON segment_travel_times (road, direction, observation_time DESC);
+
+SELECT add_retention_policy('segment_travel_times', drop_after => INTERVAL '90 days');
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS queue_event_snapshots;
+DROP TABLE IF EXISTS queue_events;
@@ -0,0 +1,44 @@
+-- Queues: contiguous stretches of slow or stationary sites on a main carriageway,
+-- tracked from the moment they form until they dissolve
+CREATE TABLE queue_events (
+    queue_id SERIAL PRIMARY KEY,
+    road TEXT NOT NULL,
+    direction road_direction NOT NULL,
+
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    -- NULL while the queue is still there
+    ended_at TIMESTAMPTZ,
+
+    -- Latest extent, the tail is the upstream end where traffic joins the queue
+    tail_site_id TEXT NOT NULL,
+    head_site_id TEXT NOT NULL,
+    tail_kilometer_point DOUBLE PRECISION NOT NULL,
+    head_kilometer_point DOUBLE PRECISION NOT NULL,
+    length_km DOUBLE PRECISION NOT NULL,
+    max_length_km DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX idx_queue_events_open
+    ON queue_events (road, direction)
+    WHERE ended_at IS NULL;
+
+CREATE INDEX idx_queue_events_started_at
+    ON queue_events (started_at DESC);
+
+-- Extent of a queue at every ingestion run it was seen
+CREATE TABLE queue_event_snapshots (
+    queue_id INTEGER NOT NULL REFERENCES queue_events (queue_id) ON DELETE CASCADE,
+    observation_time TIMESTAMPTZ NOT NULL,
+
+    tail_site_id TEXT NOT NULL,
+    head_site_id TEXT NOT NULL,
+    tail_kilometer_point DOUBLE PRECISION NOT NULL,
+    head_kilometer_point DOUBLE PRECISION NOT NULL,
+    length_km DOUBLE PRECISION NOT NULL,
+    site_count INTEGER NOT NULL,
+    min_speed DOUBLE PRECISION,
+    average_speed DOUBLE PRECISION,
+
+    PRIMARY KEY (queue_id, observation_time)
+);
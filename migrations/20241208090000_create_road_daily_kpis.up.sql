@@ -0,0 +1,22 @@
+-- Daily traffic performance per road and direction, filled by the KPI job from the
+-- hourly aggregate. Every main carriageway site stands for the road up to halfway
+-- its neighbouring sites.
+CREATE TABLE road_daily_kpis (
+    -- Local calendar day
+    day DATE NOT NULL,
+    road TEXT NOT NULL,
+    direction road_direction NOT NULL,
+
+    site_count INTEGER NOT NULL,
+    -- Length of road represented by the sites that had data
+    covered_km DOUBLE PRECISION NOT NULL,
+
+    vehicle_km DOUBLE PRECISION NOT NULL,
+    vehicle_hours DOUBLE PRECISION NOT NULL,
+    -- Vehicle-hours spent above the free-flow travel time
+    vehicle_hours_lost DOUBLE PRECISION NOT NULL,
+
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (day, road, direction)
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS emission_factors;
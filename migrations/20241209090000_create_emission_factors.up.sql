@@ -0,0 +1,16 @@
+-- Emission factors per vehicle class and speed band in grams per vehicle-kilometre.
+-- The table is replaced from the configured factor file at startup.
+CREATE TABLE emission_factors (
+    vehicle_class vehicle_class NOT NULL,
+    -- km/h, the band includes min_speed and excludes max_speed
+    min_speed DOUBLE PRECISION NOT NULL,
+    -- NULL for the open ended top band
+    max_speed DOUBLE PRECISION,
+
+    co2 DOUBLE PRECISION NOT NULL,
+    nox DOUBLE PRECISION NOT NULL,
+    pm DOUBLE PRECISION NOT NULL,
+
+    PRIMARY KEY (vehicle_class, min_speed),
+    CHECK (max_speed IS NULL OR max_speed > min_speed)
+);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS equipment_outages;
+DROP TABLE IF EXISTS sensor_health_hourly;
+DROP TABLE IF EXISTS sensor_health;
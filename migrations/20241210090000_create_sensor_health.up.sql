@@ -0,0 +1,44 @@
+-- Latest observations per location, kept up to date by every ingestion run
+CREATE TABLE sensor_health (
+    location_id INTEGER PRIMARY KEY,
+    last_observation_time TIMESTAMPTZ NOT NULL,
+    -- Latest reading that was available, valid, not faulty and without sensor error
+    last_valid_observation_time TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Reading counts per location and hour to derive the share of bad readings
+CREATE TABLE sensor_health_hourly (
+    location_id INTEGER NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+
+    reading_count INTEGER NOT NULL,
+    -- Faulty, invalid or sensor error readings of an available sensor
+    defective_count INTEGER NOT NULL,
+    unavailable_count INTEGER NOT NULL,
+
+    PRIMARY KEY (location_id, hour)
+);
+
+SELECT create_hypertable('sensor_health_hourly', 'hour');
+
+SELECT add_retention_policy('sensor_health_hourly', drop_after => INTERVAL '30 days');
+
+-- Periods in which none of the lanes of a measuring station (lve_nr) produced a valid reading
+CREATE TABLE equipment_outages (
+    outage_id SERIAL PRIMARY KEY,
+    equipment_number INTEGER NOT NULL,
+
+    -- Last valid reading before the outage, the detection time when there never was one
+    started_at TIMESTAMPTZ NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- First valid reading after the outage, NULL while it lasts
+    ended_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX idx_equipment_outages_open
+    ON equipment_outages (equipment_number)
+    WHERE ended_at IS NULL;
+
+CREATE INDEX idx_equipment_outages_started_at
+    ON equipment_outages (started_at DESC);
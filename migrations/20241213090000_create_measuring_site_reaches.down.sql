@@ -0,0 +1 @@
+DROP VIEW IF EXISTS measuring_site_reaches;
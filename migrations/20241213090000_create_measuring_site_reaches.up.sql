@@ -0,0 +1,15 @@
+-- The stretch of road each main carriageway site stands for: up to halfway its
+-- neighbours on the same road and direction, but never more than 1 km on either
+-- side, and nothing past the first and last site of a road. Queue lengths and
+-- road KPIs both use it, so they agree on how much road a reading covers.
+CREATE VIEW measuring_site_reaches AS
+SELECT
+    site_id,
+    LEAST(COALESCE((kilometer_point - LAG(kilometer_point) OVER corridor) / 2, 0), 1.0) AS reach_before_km,
+    LEAST(COALESCE((LEAD(kilometer_point) OVER corridor - kilometer_point) / 2, 0), 1.0) AS reach_after_km
+FROM measuring_sites
+WHERE main_carriageway
+    AND road IS NOT NULL
+    AND direction IS NOT NULL
+    AND kilometer_point IS NOT NULL
+WINDOW corridor AS (PARTITION BY road, direction ORDER BY kilometer_point);
@@ -0,0 +1,25 @@
+-- Measuring sites only group the lanes of the current MIV configuration, lanes and
+-- sensors that were removed no longer count towards a site. The columns are unchanged,
+-- so the views built on top of it keep working.
+CREATE OR REPLACE VIEW measuring_sites AS
+SELECT
+    ident_8 || '-' || equipment_number AS site_id,
+    equipment_number,
+    ident_8,
+    CASE WHEN ident_8 ~ '^[A-Z][0-9]{3}[0-9A-Za-z]{3}[0-9]$'
+        THEN substr(ident_8, 1, 1) || substr(ident_8, 2, 3)::INTEGER
+    END AS road,
+    CASE WHEN ident_8 ~ '^[A-Z][0-9]{3}[0-9A-Za-z]{3}[0-9]$'
+        THEN CASE WHEN substr(ident_8, 8, 1)::INTEGER % 2 = 1 THEN 'POSITIVE' ELSE 'NEGATIVE' END::road_direction
+    END AS direction,
+    ident_8 ~ '^[A-Z][0-9]{3}000[12]$' AS main_carriageway,
+    MIN(full_name) AS full_name,
+    AVG(kilometer_point) AS kilometer_point,
+    AVG(latitude) AS latitude,
+    AVG(longitude) AS longitude,
+    COUNT(*)::INTEGER AS lane_count,
+    array_agg(location_id ORDER BY lane) AS location_ids,
+    array_agg(lane ORDER BY lane) AS lanes
+FROM locations
+WHERE location_id IN (SELECT location_id FROM location_versions WHERE valid_to IS NULL)
+GROUP BY equipment_number, ident_8;
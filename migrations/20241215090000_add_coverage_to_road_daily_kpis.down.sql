@@ -0,0 +1,2 @@
+ALTER TABLE road_daily_kpis
+    DROP COLUMN IF EXISTS coverage;
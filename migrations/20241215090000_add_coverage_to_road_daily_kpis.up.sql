@@ -0,0 +1,5 @@
+-- Length weighted share of the lane-minutes of the day with a valid reading. The KPIs
+-- are not scaled up over gaps, so days with a low coverage under-report. Empty for days
+-- computed before the column existed until the KPI job recomputes them.
+ALTER TABLE road_daily_kpis
+    ADD COLUMN coverage DOUBLE PRECISION;
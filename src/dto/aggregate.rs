@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::traffic_aggregate::Resolution;
+
+use super::measurement::MeasurementDTO;
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateDTO {
+	pub location_id: i32,
+	pub bucket: DateTime<Utc>,
+
+	pub total_vehicles_passed: Option<i64>,
+	pub time_mean_speed: Option<f64>,
+	pub space_mean_speed: Option<f64>,
+	pub occupancy_rate: Option<f64>,
+
+	// Share of the expected one-minute readings that were present and valid
+	pub coverage: Option<f64>,
+	pub sample_count: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(untagged)]
+pub enum SeriesDataDTO {
+	Raw(Vec<MeasurementDTO>),
+	Aggregated(Vec<AggregateDTO>),
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesDTO {
+	pub location_id: i32,
+	pub from: DateTime<Utc>,
+	pub to: DateTime<Utc>,
+	pub resolution: Resolution,
+	pub data: SeriesDataDTO,
+}
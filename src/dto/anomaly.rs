@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::traffic_anomaly::AnomalyMetric;
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AnomalyDTO {
+	pub location_id: i32,
+	pub metric: AnomalyMetric,
+	pub started_at: DateTime<Utc>,
+	pub last_seen_at: DateTime<Utc>,
+	// None while the anomaly lasts
+	pub ended_at: Option<DateTime<Utc>>,
+
+	// Of the most severe reading over the 15 minutes up to it, intensity in vehicles per 15 minutes
+	pub observed_value: f64,
+	pub expected_value: f64,
+	pub lower_bound: Option<f64>,
+	pub upper_bound: Option<f64>,
+	pub severity: f64,
+	pub detected_at: DateTime<Utc>,
+
+	// Location
+	pub descriptive_id: Option<String>,
+	pub full_name: Option<String>,
+	pub latitude: Option<f64>,
+	pub longitude: Option<f64>,
+}
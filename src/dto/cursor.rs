@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Opaque pagination cursor pointing at the last returned measurement.
+/// Measurements are ordered by (observation_time, location_id) descending.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct MeasurementCursor {
+	pub observation_time: DateTime<Utc>,
+	pub location_id: i32,
+}
+
+impl MeasurementCursor {
+	pub fn encode(&self) -> String {
+		format!("{}:{}", self.observation_time.timestamp_micros(), self.location_id)
+			.bytes()
+			.map(|byte| format!("{:02x}", byte))
+			.collect()
+	}
+
+	pub fn decode(cursor: &str) -> Option<Self> {
+		let bytes = cursor
+			.as_bytes()
+			.chunks(2)
+			.map(|pair| {
+				std::str::from_utf8(pair)
+					.ok()
+					.filter(|pair| pair.len() == 2)
+					.and_then(|pair| u8::from_str_radix(pair, 16).ok())
+			})
+			.collect::<Option<Vec<u8>>>()?;
+
+		let value = String::from_utf8(bytes).ok()?;
+		let (micros, location_id) = value.split_once(':')?;
+
+		Some(MeasurementCursor {
+			observation_time: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+			location_id: location_id.parse().ok()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn cursor() -> MeasurementCursor {
+		MeasurementCursor {
+			observation_time: DateTime::from_timestamp_micros(1_733_130_000_123_456).unwrap(),
+			location_id: 3638,
+		}
+	}
+
+	#[test]
+	fn decodes_what_it_encodes() {
+		assert_eq!(MeasurementCursor::decode(&cursor().encode()), Some(cursor()));
+	}
+
+	#[test]
+	fn encodes_as_hex() {
+		let encoded = cursor().encode();
+
+		assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+		assert_eq!(encoded.len() % 2, 0);
+	}
+
+	#[test]
+	fn rejects_malformed_cursors() {
+		let encoded = cursor().encode();
+
+		for malformed in ["", "zz", "abc", "é1", &encoded[..encoded.len() - 1], "313233", "3a3132", "31323a6162"] {
+			assert_eq!(MeasurementCursor::decode(malformed), None, "{}", malformed);
+		}
+	}
+}
@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Emissions are in grams emitted along the road length the measuring site of the lane
+// represents: the vehicles passing the location times their emission factor in grams
+// per vehicle-kilometre times that length
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EmissionDTO {
+	pub hour: DateTime<Utc>,
+	// Kilometres of road the lane stands for, missing outside a main carriageway site
+	pub represented_km: Option<f64>,
+
+	pub vehicles: i64,
+	// Vehicles without a speed, matching emission factor or represented length, not
+	// part of the estimate
+	pub unestimated_vehicles: i64,
+
+	pub co2: f64,
+	pub nox: f64,
+	pub pm: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationEmissionDTO {
+	pub location_id: i32,
+	pub descriptive_id: Option<String>,
+	pub full_name: Option<String>,
+	pub latitude: Option<f64>,
+	pub longitude: Option<f64>,
+	pub represented_km: Option<f64>,
+
+	pub vehicles: i64,
+	pub unestimated_vehicles: i64,
+
+	pub co2: f64,
+	pub nox: f64,
+	pub pm: f64,
+}
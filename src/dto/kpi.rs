@@ -0,0 +1,27 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::models::site::RoadDirection;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RoadKpiDTO {
+	// First day of the day or month
+	pub period_start: NaiveDate,
+	pub road: String,
+	pub direction: RoadDirection,
+	// Days with data in the period
+	pub day_count: i64,
+
+	pub site_count: i32,
+	pub covered_km: f64,
+
+	pub vehicle_km: f64,
+	pub vehicle_hours: f64,
+	pub vehicle_hours_lost: f64,
+	// Vehicle-kilometres over vehicle-hours
+	pub average_speed: Option<f64>,
+	// Share of the road length and hours with readings, averaged over the days with data.
+	// The totals are not scaled up, below 1 they under-report the traffic.
+	pub coverage: Option<f64>,
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationDTO {
+	pub location_id: i32,
+	pub descriptive_id: String,
+	pub full_name: String,
+	pub ident_8: String,
+	pub equipment_number: i32,
+	pub kilometer_point: Option<f64>,
+	pub lane: String,
+	pub lambert_x: f64,
+	pub lambert_y: f64,
+	pub latitude: f64,
+	pub longitude: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NearestLocationDTO {
+	pub location_id: i32,
+	pub descriptive_id: String,
+	pub full_name: String,
+	pub ident_8: String,
+	pub equipment_number: i32,
+	pub kilometer_point: Option<f64>,
+	pub lane: String,
+	pub lambert_x: f64,
+	pub lambert_y: f64,
+	pub latitude: f64,
+	pub longitude: f64,
+
+	// Distance in meters
+	pub distance: f64,
+}
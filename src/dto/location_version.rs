@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationVersionDTO {
+	pub location_id: i32,
+	pub descriptive_id: String,
+	pub full_name: String,
+	pub ident_8: String,
+	pub equipment_number: i32,
+	pub kilometer_point: Option<f64>,
+	pub lane: String,
+	pub lambert_x: f64,
+	pub lambert_y: f64,
+	pub latitude: f64,
+	pub longitude: f64,
+
+	// Validity
+	pub valid_from: DateTime<Utc>,
+	pub valid_to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationConfigurationVersionDTO {
+	pub config_time: DateTime<Utc>,
+	pub imported_at: DateTime<Utc>,
+	pub location_count: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationMoveDTO {
+	pub location_id: i32,
+	pub descriptive_id: String,
+	pub full_name: String,
+	pub from_latitude: f64,
+	pub from_longitude: f64,
+	pub to_latitude: f64,
+	pub to_longitude: f64,
+	pub distance: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationConfigurationChangesDTO {
+	pub from: DateTime<Utc>,
+	pub to: DateTime<Utc>,
+	pub added: Vec<LocationVersionDTO>,
+	pub removed: Vec<LocationVersionDTO>,
+	pub moved: Vec<LocationMoveDTO>,
+}
@@ -1,7 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::models::{congestion::CongestionLevel, plausibility::PlausibilityFlag, traffic_measurement::DataQuality};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct MeasurementDTO {
     pub location_id: i32,
@@ -10,11 +12,35 @@ pub struct MeasurementDTO {
     // Calculated data
     pub occupancy_rate: Option<i32>,
     pub availability_rate: Option<i32>,
+    pub instability: Option<i32>,
 	pub total_vehicles_passed: Option<i32>,
 	pub average_speed: Option<i32>,
 	pub max_speed: Option<i32>,
+	pub time_mean_speed: Option<f64>,
+	pub space_mean_speed: Option<f64>,
+	pub data_quality: Option<DataQuality>,
+
+	// Sensor status
+	pub current_publication: Option<bool>,
+	pub available: Option<bool>,
+	pub faulty: Option<bool>,
+	pub valid: Option<bool>,
+	// Failed plausibility checks, empty when all passed
+	pub plausibility_flags: Option<Vec<PlausibilityFlag>>,
+
+	// Congestion
+	pub free_flow_speed: Option<f64>,
+	pub congestion_level: Option<CongestionLevel>,
 
-	// Location
-	pub latitude: f64,
-	pub longitude: f64,
+	// Location, missing when no configuration version covers the observation time
+	pub descriptive_id: Option<String>,
+	pub full_name: Option<String>,
+	pub ident_8: Option<String>,
+	pub equipment_number: Option<i32>,
+	pub kilometer_point: Option<f64>,
+	pub lane: Option<String>,
+	pub lambert_x: Option<f64>,
+	pub lambert_y: Option<f64>,
+	pub latitude: Option<f64>,
+	pub longitude: Option<f64>,
 }
@@ -1 +1,14 @@
 pub mod measurement;
+pub mod location_version;
+pub mod cursor;
+pub mod page;
+pub mod location;
+pub mod aggregate;
+pub mod profile;
+pub mod anomaly;
+pub mod site;
+pub mod travel_time;
+pub mod queue;
+pub mod kpi;
+pub mod emission;
+pub mod sensor_health;
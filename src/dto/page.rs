@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PageDTO<T, Q> {
+	pub data: Vec<T>,
+	pub meta: PageMetaDTO<Q>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PageMetaDTO<Q> {
+	pub next_cursor: Option<String>,
+	// The query parameters as they were applied, including defaults
+	pub query: Q,
+	pub freshness: FreshnessDTO,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FreshnessDTO {
+	pub latest_observation_time: Option<DateTime<Utc>>,
+	pub generated_at: DateTime<Utc>,
+}
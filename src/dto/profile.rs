@@ -0,0 +1,45 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficProfileSlotDTO {
+	// ISO weekday, 1 is monday
+	pub weekday: i16,
+	// Start of the 15 minute slot in local time
+	pub slot_start: NaiveTime,
+	pub sample_count: i32,
+
+	pub average_intensity: Option<f64>,
+	pub intensity_p10: Option<f64>,
+	pub intensity_p50: Option<f64>,
+	pub intensity_p90: Option<f64>,
+
+	pub average_speed: Option<f64>,
+	pub speed_p10: Option<f64>,
+	pub speed_p50: Option<f64>,
+	pub speed_p90: Option<f64>,
+
+	pub average_occupancy: Option<f64>,
+	pub occupancy_p10: Option<f64>,
+	pub occupancy_p50: Option<f64>,
+	pub occupancy_p90: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficProfileRefreshDTO {
+	pub refreshed_at: DateTime<Utc>,
+	pub window_start: DateTime<Utc>,
+	pub window_end: DateTime<Utc>,
+	pub time_zone: String,
+	pub profile_count: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficProfileDTO {
+	pub location_id: i32,
+	pub refresh: TrafficProfileRefreshDTO,
+	pub slots: Vec<TrafficProfileSlotDTO>,
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::site::RoadDirection;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEventDTO {
+	pub queue_id: i32,
+	pub road: String,
+	pub direction: RoadDirection,
+
+	pub started_at: DateTime<Utc>,
+	pub last_seen_at: DateTime<Utc>,
+	pub ended_at: Option<DateTime<Utc>>,
+
+	pub tail_site_id: String,
+	pub tail_name: Option<String>,
+	pub tail_kilometer_point: f64,
+	pub head_site_id: String,
+	pub head_name: Option<String>,
+	pub head_kilometer_point: f64,
+	pub length_km: f64,
+	pub max_length_km: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueSnapshotDTO {
+	pub observation_time: DateTime<Utc>,
+	pub tail_site_id: String,
+	pub head_site_id: String,
+	pub tail_kilometer_point: f64,
+	pub head_kilometer_point: f64,
+	pub length_km: f64,
+	pub site_count: i32,
+	pub min_speed: Option<f64>,
+	pub average_speed: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEventDetailDTO {
+	#[serde(flatten)]
+	pub event: QueueEventDTO,
+	pub snapshots: Vec<QueueSnapshotDTO>,
+}
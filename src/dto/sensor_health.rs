@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::sensor_health::SensorStatus;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationHealthDTO {
+	pub location_id: i32,
+	pub descriptive_id: String,
+	pub full_name: String,
+	// lve_nr
+	pub equipment_number: i32,
+	pub latitude: f64,
+	pub longitude: f64,
+
+	pub status: SensorStatus,
+	pub last_observation_time: Option<DateTime<Utc>>,
+	pub last_valid_observation_time: Option<DateTime<Utc>>,
+
+	// Readings within the health window
+	pub reading_count: i64,
+	pub defective_count: i64,
+	pub unavailable_count: i64,
+	pub defective_share: Option<f64>,
+	pub unavailable_share: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EquipmentHealthDTO {
+	pub equipment_number: i32,
+	pub status: SensorStatus,
+
+	pub location_count: usize,
+	pub degraded_count: usize,
+	pub silent_count: usize,
+	pub last_valid_observation_time: Option<DateTime<Utc>>,
+
+	pub reading_count: i64,
+	pub defective_share: Option<f64>,
+	pub unavailable_share: Option<f64>,
+	// Start of the ongoing outage of the whole station
+	pub outage_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SensorHealthSummaryDTO {
+	// Latest observation of the network, silence is measured against it
+	pub reference_time: Option<DateTime<Utc>>,
+	pub window_hours: i64,
+
+	pub location_count: usize,
+	pub ok_count: usize,
+	pub degraded_count: usize,
+	pub silent_count: usize,
+
+	pub equipment_count: usize,
+	pub equipment_outage_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SensorHealthDTO {
+	pub summary: SensorHealthSummaryDTO,
+	pub locations: Vec<LocationHealthDTO>,
+	pub equipment: Vec<EquipmentHealthDTO>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EquipmentOutageDTO {
+	pub outage_id: i32,
+	pub equipment_number: i32,
+
+	pub started_at: DateTime<Utc>,
+	pub detected_at: DateTime<Utc>,
+	pub ended_at: Option<DateTime<Utc>>,
+}
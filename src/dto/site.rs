@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::site::RoadDirection;
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteDTO {
+	pub site_id: String,
+	pub equipment_number: i32,
+	pub ident_8: String,
+
+	// Parsed from Ident_8, e.g. A14
+	pub road: Option<String>,
+	pub direction: Option<RoadDirection>,
+	pub main_carriageway: bool,
+
+	pub full_name: String,
+	pub kilometer_point: Option<f64>,
+	pub latitude: f64,
+	pub longitude: f64,
+
+	// Lanes, ordered by lane code
+	pub lane_count: i32,
+	pub location_ids: Vec<i32>,
+	pub lanes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteMeasurementDTO {
+	pub observation_time: DateTime<Utc>,
+	// Lanes that reported a valid reading
+	pub lane_count: i32,
+
+	// Summed across lanes
+	pub total_vehicles_passed: Option<i64>,
+	// Intensity-weighted across lanes
+	pub time_mean_speed: Option<f64>,
+	pub space_mean_speed: Option<f64>,
+	// Averaged across lanes
+	pub occupancy_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CorridorSiteDTO {
+	pub site_id: String,
+	pub full_name: String,
+	pub kilometer_point: f64,
+	pub latitude: f64,
+	pub longitude: f64,
+	pub lane_count: i32,
+
+	// Latest readings combined across the lanes, empty when the site is not reporting
+	pub observation_time: Option<DateTime<Utc>>,
+	pub reporting_lane_count: i32,
+	pub total_vehicles_passed: Option<i64>,
+	pub time_mean_speed: Option<f64>,
+	pub space_mean_speed: Option<f64>,
+	pub occupancy_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContourCellDTO {
+	pub site_id: String,
+	pub bucket: DateTime<Utc>,
+	pub space_mean_speed: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContourSiteDTO {
+	pub site_id: String,
+	pub full_name: String,
+	pub kilometer_point: Option<f64>,
+}
+
+/// Time-space matrix of speeds: `speeds[i][j]` is the speed at `times[i]` for `sites[j]`,
+/// sites are in driving order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedContourDTO {
+	pub road: String,
+	pub direction: RoadDirection,
+	pub from: DateTime<Utc>,
+	pub to: DateTime<Utc>,
+	pub bucket_minutes: i32,
+	pub sites: Vec<ContourSiteDTO>,
+	pub times: Vec<DateTime<Utc>>,
+	pub speeds: Vec<Vec<Option<f64>>>,
+}
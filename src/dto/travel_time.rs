@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::site::RoadDirection;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentTravelTimeDTO {
+	pub from_site_id: String,
+	pub to_site_id: String,
+	pub observation_time: DateTime<Utc>,
+	pub from_kilometer_point: f64,
+	pub to_kilometer_point: f64,
+	pub length_km: f64,
+	pub from_speed: f64,
+	pub to_speed: f64,
+	pub travel_time_seconds: f64,
+
+	// Part of the segment that lies on the route
+	pub route_km: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteTravelTimeDTO {
+	pub road: String,
+	pub direction: RoadDirection,
+	pub from_km: f64,
+	pub to_km: f64,
+	pub at: DateTime<Utc>,
+
+	// None when the measured segments do not cover the whole route
+	pub travel_time_seconds: Option<f64>,
+	// Length of the route covered by measured segments
+	pub covered_km: f64,
+	pub complete: bool,
+	pub segments: Vec<SegmentTravelTimeDTO>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteTravelTimePointDTO {
+	pub observation_time: DateTime<Utc>,
+	// None when the measured segments do not cover the whole route
+	pub travel_time_seconds: Option<f64>,
+	pub covered_km: f64,
+	pub complete: bool,
+}
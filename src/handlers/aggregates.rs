@@ -0,0 +1,120 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Deserialize;
+
+use crate::{dto::aggregate::{SeriesDTO, SeriesDataDTO}, errors::AppError, models::{traffic_aggregate::{AggregateBucket, FindAggregatesParams, Resolution, TrafficAggregate}, traffic_measurement::{FindMeasurementsByLocationIdParams, TrafficMeasurement}}, state::AppState};
+
+use super::{measurements::FindByLocationIdPathParams, validation::{bad_request, parse_location_id, validate_bounded_time_range}};
+
+const DEFAULT_SERIES_RANGE_DAYS: i64 = 1;
+const MAX_SERIES_RANGE_DAYS: i64 = 3660;
+const DEFAULT_MAX_POINTS: i64 = 1000;
+const MAX_MAX_POINTS: i64 = 10_000;
+
+#[derive(Deserialize)]
+pub struct FindAggregatesQueryParams {
+	// One of 15m, 1h or 1d
+	bucket: AggregateBucket,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/locations/{location_id}/aggregates")]
+pub async fn find_by_location_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindByLocationIdPathParams>,
+	query: web::Query<FindAggregatesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let location_id = parse_location_id(&params.location_id)?;
+
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		query.bucket.default_range(),
+		query.bucket.max_range(),
+	)?;
+
+	let aggregates = TrafficAggregate::get_by_location_id(&state.pool, location_id, FindAggregatesParams {
+		bucket: query.bucket,
+		from,
+		to,
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(aggregates))
+}
+
+#[derive(Deserialize)]
+pub struct FindSeriesQueryParams {
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+	// Upper bound on the number of points, used to pick the resolution
+	max_points: Option<i64>,
+	// Forces a resolution instead of picking one
+	resolution: Option<Resolution>,
+}
+
+#[get("/locations/{location_id}/series")]
+pub async fn find_series_by_location_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindByLocationIdPathParams>,
+	query: web::Query<FindSeriesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let location_id = parse_location_id(&params.location_id)?;
+
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::days(DEFAULT_SERIES_RANGE_DAYS),
+		Duration::days(MAX_SERIES_RANGE_DAYS),
+	)?;
+
+	let max_points = query.max_points.unwrap_or(DEFAULT_MAX_POINTS);
+	if !(1..=MAX_MAX_POINTS).contains(&max_points) {
+		return Err(bad_request("INVALID_MAX_POINTS", format!("'max_points' must be between 1 and {}", MAX_MAX_POINTS)));
+	}
+
+	let resolution = query.resolution
+		.unwrap_or_else(|| Resolution::select(to - from, max_points));
+
+	if resolution.point_count(to - from) > MAX_MAX_POINTS {
+		return Err(bad_request("INVALID_RESOLUTION", format!("The requested resolution would return more than {} points", MAX_MAX_POINTS)));
+	}
+
+	let data = match resolution.aggregate_bucket() {
+		Some(bucket) => SeriesDataDTO::Aggregated(
+			TrafficAggregate::get_by_location_id(&state.pool, location_id, FindAggregatesParams {
+				bucket,
+				from,
+				to,
+			})
+				.await?
+		),
+		None if resolution == Resolution::Raw => {
+			let mut measurements = TrafficMeasurement::get_by_location_id(&state.pool, location_id, FindMeasurementsByLocationIdParams {
+				from: Some(from),
+				to: Some(to),
+				cursor: None,
+				limit: MAX_MAX_POINTS,
+				include_invalid: false,
+			})
+				.await?;
+
+			// Series are returned oldest first
+			measurements.reverse();
+			SeriesDataDTO::Raw(measurements)
+		},
+		None => SeriesDataDTO::Aggregated(
+			TrafficMeasurement::get_downsampled_by_location_id(&state.pool, location_id, from, to, resolution.duration())
+				.await?
+		),
+	};
+
+	Ok(HttpResponse::Ok().json(SeriesDTO {
+		location_id,
+		from,
+		to,
+		resolution,
+		data,
+	}))
+}
@@ -0,0 +1,55 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+
+use crate::{errors::AppError, models::traffic_anomaly::{AnomalyMetric, FindAnomaliesParams, TrafficAnomaly}, state::AppState};
+
+use super::validation::{bad_request, parse_bbox, validate_limit, validate_location, validate_time_range};
+
+const DEFAULT_MIN_SEVERITY: f64 = 3.0;
+
+#[derive(Deserialize)]
+pub struct FindAnomaliesQueryParams {
+	lat: Option<f64>,
+	lon: Option<f64>,
+	// Radius in meters, only used when both lat and lon are given
+	radius: Option<f64>,
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+	metric: Option<AnomalyMetric>,
+	min_severity: Option<f64>,
+	limit: Option<i64>,
+}
+
+#[get("/anomalies")]
+pub async fn find_all(
+	state: web::Data<AppState>,
+	query: web::Query<FindAnomaliesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let radius = validate_location(query.lat, query.lon, query.radius)?;
+	let bbox = parse_bbox(&query.bbox)?;
+	let (from, to) = validate_time_range(query.from, query.to)?;
+	let limit = validate_limit(query.limit)?;
+	let min_severity = query.min_severity.unwrap_or(DEFAULT_MIN_SEVERITY);
+
+	if min_severity.is_nan() || min_severity < 0.0 {
+		return Err(bad_request("INVALID_SEVERITY", "'min_severity' can not be negative".to_owned()));
+	}
+
+	let anomalies = TrafficAnomaly::get_all(&state.pool, FindAnomaliesParams {
+		lat: query.lat,
+		lon: query.lon,
+		radius,
+		bbox,
+		from,
+		to,
+		metric: query.metric,
+		min_severity,
+		limit,
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(anomalies))
+}
@@ -0,0 +1,73 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Deserialize;
+
+use crate::{dto::location_version::LocationConfigurationChangesDTO, errors::AppError, models::location_version::LocationVersion, state::AppState};
+
+use super::validation::bad_request;
+
+#[get("/configuration/versions")]
+pub async fn find_versions(
+	state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+	let versions = LocationVersion::get_versions(&state.pool)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(versions))
+}
+
+#[derive(Deserialize)]
+pub struct FindLocationsQueryParams {
+	at: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/configuration/locations")]
+pub async fn find_locations(
+	state: web::Data<AppState>,
+	query: web::Query<FindLocationsQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let at = query.at
+		.map(|at| at.with_timezone(&Utc))
+		.unwrap_or_else(Utc::now);
+
+	let locations = LocationVersion::get_at(&state.pool, at)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(locations))
+}
+
+#[derive(Deserialize)]
+pub struct FindChangesQueryParams {
+	from: DateTime<FixedOffset>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/configuration/changes")]
+pub async fn find_changes(
+	state: web::Data<AppState>,
+	query: web::Query<FindChangesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let from = query.from.with_timezone(&Utc);
+	let to = query.to
+		.map(|to| to.with_timezone(&Utc))
+		.unwrap_or_else(Utc::now);
+
+	if from > to {
+		return Err(bad_request("INVALID_RANGE", "'from' must be before 'to'".to_owned()));
+	}
+
+	let added = LocationVersion::get_added(&state.pool, from, to)
+		.await?;
+	let removed = LocationVersion::get_added(&state.pool, to, from)
+		.await?;
+	let moved = LocationVersion::get_moved(&state.pool, from, to)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(LocationConfigurationChangesDTO {
+		from,
+		to,
+		added,
+		removed,
+		moved,
+	}))
+}
@@ -0,0 +1,33 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+
+use crate::{errors::AppError, models::{congestion::CongestionLevel, current_traffic_measurement::{CurrentTrafficMeasurement, FindCurrentMeasurementsParams, MAX_READING_AGE_MINUTES}}, state::AppState};
+
+use super::validation::parse_bbox;
+
+#[derive(Deserialize)]
+pub struct FindCongestionQueryParams {
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+	// Least congested level to include, defaults to SLOW
+	level: Option<CongestionLevel>,
+}
+
+#[get("/congestion")]
+pub async fn find_all(
+	state: web::Data<AppState>,
+	query: web::Query<FindCongestionQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let bbox = parse_bbox(&query.bbox)?;
+
+	let measurements = CurrentTrafficMeasurement::get_all(&state.pool, FindCurrentMeasurementsParams {
+		bbox,
+		include_invalid: false,
+		since: Some(Utc::now() - Duration::minutes(MAX_READING_AGE_MINUTES)),
+		min_congestion_level: Some(query.level.unwrap_or(CongestionLevel::Slow)),
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(measurements))
+}
@@ -0,0 +1,83 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Deserialize;
+
+use crate::{errors::AppError, models::emission::{Emission, FindAreaEmissionsParams}, state::AppState};
+
+use super::{measurements::FindByLocationIdPathParams, validation::{bad_request, parse_bbox, parse_location_id, validate_bounded_time_range, validate_location}};
+
+const DEFAULT_EMISSION_RANGE_DAYS: i64 = 1;
+const MAX_LOCATION_EMISSION_RANGE_DAYS: i64 = 31;
+// Area totals scan every location in the area, keep them to a week
+const MAX_AREA_EMISSION_RANGE_DAYS: i64 = 7;
+
+#[derive(Deserialize)]
+pub struct FindEmissionsQueryParams {
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/locations/{location_id}/emissions")]
+pub async fn find_by_location_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindByLocationIdPathParams>,
+	query: web::Query<FindEmissionsQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let location_id = parse_location_id(&params.location_id)?;
+
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::days(DEFAULT_EMISSION_RANGE_DAYS),
+		Duration::days(MAX_LOCATION_EMISSION_RANGE_DAYS),
+	)?;
+
+	let emissions = Emission::get_hourly_by_location_id(&state.pool, location_id, from, to)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(emissions))
+}
+
+#[derive(Deserialize)]
+pub struct FindAreaEmissionsQueryParams {
+	lat: Option<f64>,
+	lon: Option<f64>,
+	// Radius in meters, only used when both lat and lon are given
+	radius: Option<f64>,
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/emissions")]
+pub async fn find_by_area(
+	state: web::Data<AppState>,
+	query: web::Query<FindAreaEmissionsQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let radius = validate_location(query.lat, query.lon, query.radius)?;
+	let bbox = parse_bbox(&query.bbox)?;
+
+	if query.lat.is_none() && bbox.is_none() {
+		return Err(bad_request("MISSING_AREA", "Either 'lat' and 'lon' or 'bbox' is required".to_owned()));
+	}
+
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::days(DEFAULT_EMISSION_RANGE_DAYS),
+		Duration::days(MAX_AREA_EMISSION_RANGE_DAYS),
+	)?;
+
+	let emissions = Emission::get_by_area(&state.pool, FindAreaEmissionsParams {
+		lat: query.lat,
+		lon: query.lon,
+		radius,
+		bbox,
+		from,
+		to,
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(emissions))
+}
@@ -0,0 +1,80 @@
+use actix_web::{get, web, HttpResponse, Result};
+use serde::Deserialize;
+
+use crate::{errors::AppError, models::location::{FindLocationsParams, Location}, state::AppState};
+
+use super::{measurements::FindByLocationIdPathParams, validation::{bad_request, location_not_found, parse_bbox, parse_location_id, validate_coordinates, validate_location}};
+
+const DEFAULT_NEAREST_COUNT: i64 = 10;
+const MAX_NEAREST_COUNT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct FindLocationsQueryParams {
+	lat: Option<f64>,
+	lon: Option<f64>,
+	// Radius in meters, only used when both lat and lon are given
+	radius: Option<f64>,
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+	// Case insensitive match on the full name or descriptive id
+	name: Option<String>,
+}
+
+#[get("/locations")]
+pub async fn find_all(
+	state: web::Data<AppState>,
+	query: web::Query<FindLocationsQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let radius = validate_location(query.lat, query.lon, query.radius)?;
+	let bbox = parse_bbox(&query.bbox)?;
+
+	let locations = Location::get_all(&state.pool, FindLocationsParams {
+		lat: query.lat,
+		lon: query.lon,
+		radius,
+		bbox,
+		name: query.name.clone().filter(|name| !name.trim().is_empty()),
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(locations))
+}
+
+#[derive(Deserialize)]
+pub struct FindNearestQueryParams {
+	lat: f64,
+	lon: f64,
+	count: Option<i64>,
+}
+
+#[get("/locations/nearest")]
+pub async fn find_nearest(
+	state: web::Data<AppState>,
+	query: web::Query<FindNearestQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	validate_coordinates(query.lat, query.lon)?;
+	let count = query.count.unwrap_or(DEFAULT_NEAREST_COUNT);
+
+	if !(1..=MAX_NEAREST_COUNT).contains(&count) {
+		return Err(bad_request("INVALID_COUNT", format!("'count' must be between 1 and {}", MAX_NEAREST_COUNT)));
+	}
+
+	let locations = Location::get_nearest(&state.pool, query.lat, query.lon, count)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(locations))
+}
+
+#[get("/locations/{location_id}")]
+pub async fn find_by_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindByLocationIdPathParams>,
+) -> Result<HttpResponse, AppError> {
+	let location_id = parse_location_id(&params.location_id)?;
+
+	let location = Location::get_by_id(&state.pool, location_id)
+		.await?
+		.ok_or_else(|| location_not_found(&params.location_id))?;
+
+	Ok(HttpResponse::Ok().json(location))
+}
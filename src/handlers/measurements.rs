@@ -0,0 +1,256 @@
+use actix_web::{get, http::{header, StatusCode}, web, HttpRequest, HttpResponse, Result};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{dto::{cursor::MeasurementCursor, measurement::MeasurementDTO, page::{FreshnessDTO, PageDTO, PageMetaDTO}}, errors::{AppError, AppErrorValue}, models::{current_traffic_measurement::{CurrentTrafficMeasurement, FindCurrentMeasurementsParams}, traffic_measurement::{FindMeasurementsByLocationIdParams, FindMeasurementsParams, TrafficMeasurement}, traffic_measurement_class::{parse_vehicle_classes, FindMeasurementClassesParams, TrafficMeasurementClass, VEHICLE_CLASS_NAMES}}, state::AppState};
+
+use super::validation::{bad_request, parse_bbox, parse_location_id, validate_limit, validate_location, validate_time_range};
+
+#[derive(Deserialize)]
+pub struct FindAllQueryParams {
+	lat: Option<f64>,
+	lon: Option<f64>,
+	// Radius in meters, only used when both lat and lon are given
+	radius: Option<f64>,
+	// RFC 3339 timestamps with timezone, the range is [from, to)
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+	limit: Option<i64>,
+	include_invalid: Option<bool>,
+	// Opaque cursor from `meta.nextCursor` of the previous page
+	cursor: Option<String>,
+}
+
+/// The query parameters as they were applied. Serialized into the response
+/// meta and into the `Link` header, so the names match `FindAllQueryParams`.
+#[derive(Serialize, Debug, Clone)]
+pub struct EffectiveQueryParams {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	lat: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	lon: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	radius: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	from: Option<DateTime<Utc>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	to: Option<DateTime<Utc>>,
+	limit: i64,
+	include_invalid: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	cursor: Option<String>,
+}
+
+fn decode_cursor(cursor: &Option<String>) -> Result<Option<MeasurementCursor>, AppError> {
+	cursor
+		.as_deref()
+		.map(|cursor| MeasurementCursor::decode(cursor).ok_or_else(|| bad_request("INVALID_CURSOR", "'cursor' is not a valid cursor".to_owned())))
+		.transpose()
+}
+
+/// Wraps a page of measurements (fetched with `limit + 1` rows) in the response
+/// envelope and adds RFC 8288 `Link` headers for the current and next page.
+fn paginated_response(
+	req: &HttpRequest,
+	mut measurements: Vec<MeasurementDTO>,
+	query: EffectiveQueryParams,
+	latest_observation_time: Option<DateTime<Utc>>,
+) -> Result<HttpResponse, AppError> {
+	let has_next_page = measurements.len() as i64 > query.limit;
+	measurements.truncate(query.limit as usize);
+
+	let next_cursor = measurements
+		.last()
+		.filter(|_| has_next_page)
+		.map(|last| MeasurementCursor {
+			observation_time: last.observation_time,
+			location_id: last.location_id,
+		}.encode());
+
+	let connection_info = req.connection_info();
+	let base_url = format!("{}://{}{}", connection_info.scheme(), connection_info.host(), req.path());
+	let to_query_string = |query: &EffectiveQueryParams| serde_urlencoded::to_string(query)
+		.map_err(|err| AppError::InternalServerError(AppErrorValue {
+			message: err.to_string(),
+			status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+			code: "URL_ENCODE_ERROR".to_owned(),
+			..Default::default()
+		}));
+
+	let mut links = vec![format!("<{}?{}>; rel=\"self\"", base_url, to_query_string(&query)?)];
+	if let Some(next_cursor) = &next_cursor {
+		let next_query = EffectiveQueryParams {
+			cursor: Some(next_cursor.clone()),
+			..query.clone()
+		};
+		links.push(format!("<{}?{}>; rel=\"next\"", base_url, to_query_string(&next_query)?));
+	}
+
+	Ok(HttpResponse::Ok()
+		.insert_header((header::LINK, links.join(", ")))
+		.json(PageDTO {
+			data: measurements,
+			meta: PageMetaDTO {
+				next_cursor,
+				query,
+				freshness: FreshnessDTO {
+					latest_observation_time,
+					generated_at: Utc::now(),
+				},
+			},
+		}))
+}
+
+#[get("/measurements")]
+pub async fn find_all(
+	req: HttpRequest,
+	state: web::Data<AppState>,
+	query: web::Query<FindAllQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let lat = query.lat;
+	let lon = query.lon;
+	let radius = validate_location(lat, lon, query.radius)?;
+	let (from, to) = validate_time_range(query.from, query.to)?;
+	let limit = validate_limit(query.limit)?;
+	let include_invalid = query.include_invalid.unwrap_or(false);
+	let cursor = decode_cursor(&query.cursor)?;
+
+	let measurements = TrafficMeasurement::get_recent(&state.pool, FindMeasurementsParams {
+		lat,
+		lon,
+		radius,
+		from,
+		to,
+		cursor,
+		limit: limit + 1,
+		include_invalid
+	})
+		.await?;
+	let latest_observation_time = CurrentTrafficMeasurement::get_latest_observation_time(&state.pool, None)
+		.await?;
+
+	paginated_response(&req, measurements, EffectiveQueryParams {
+		lat,
+		lon,
+		radius: lat.and(Some(radius)),
+		from,
+		to,
+		limit,
+		include_invalid,
+		cursor: query.cursor.clone(),
+	}, latest_observation_time)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FindByLocationIdPathParams {
+	pub location_id: String,
+}
+
+#[get("/locations/{location_id}/measurements")]
+pub async fn find_by_location_id(
+	req: HttpRequest,
+	state: web::Data<AppState>,
+	query: web::Query<FindAllQueryParams>,
+	params: web::Path<FindByLocationIdPathParams>,
+) -> Result<HttpResponse, AppError> {
+	let location_id = parse_location_id(&params.location_id)?;
+	let (from, to) = validate_time_range(query.from, query.to)?;
+	let limit = validate_limit(query.limit)?;
+	let include_invalid = query.include_invalid.unwrap_or(false);
+	let cursor = decode_cursor(&query.cursor)?;
+
+	let measurements = TrafficMeasurement::get_by_location_id(&state.pool, location_id, FindMeasurementsByLocationIdParams {
+		from,
+		to,
+		cursor,
+		limit: limit + 1,
+		include_invalid
+	})
+		.await?;
+	let latest_observation_time = CurrentTrafficMeasurement::get_latest_observation_time(&state.pool, Some(location_id))
+		.await?;
+
+	paginated_response(&req, measurements, EffectiveQueryParams {
+		lat: None,
+		lon: None,
+		radius: None,
+		from,
+		to,
+		limit,
+		include_invalid,
+		cursor: query.cursor.clone(),
+	}, latest_observation_time)
+}
+
+#[derive(Deserialize)]
+pub struct FindClassesQueryParams {
+	// RFC 3339 timestamps with timezone, the range is [from, to)
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+	// Comma separated, e.g. RIGID_TRUCKS,ARTICULATED_TRUCKS, all classes when omitted
+	vehicle_class: Option<String>,
+	limit: Option<i64>,
+}
+
+#[get("/locations/{location_id}/measurements/classes")]
+pub async fn find_classes_by_location_id(
+	state: web::Data<AppState>,
+	query: web::Query<FindClassesQueryParams>,
+	params: web::Path<FindByLocationIdPathParams>,
+) -> Result<HttpResponse, AppError> {
+	let location_id = parse_location_id(&params.location_id)?;
+	let (from, to) = validate_time_range(query.from, query.to)?;
+	let limit = validate_limit(query.limit)?;
+	let vehicle_classes = query.vehicle_class
+		.as_deref()
+		.map(|vehicle_class| parse_vehicle_classes(vehicle_class)
+			.ok_or_else(|| bad_request("INVALID_VEHICLE_CLASS", format!("'vehicle_class' must be a comma separated list of {}", VEHICLE_CLASS_NAMES.join(", ")))))
+		.transpose()?;
+
+	let measurements = TrafficMeasurementClass::get_by_location_id(&state.pool, location_id, FindMeasurementClassesParams {
+		from,
+		to,
+		vehicle_classes,
+		limit,
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(measurements))
+}
+
+// Default and upper bound of the age of a reading in the latest snapshot
+const DEFAULT_LATEST_MAX_AGE_MINUTES: i64 = 15;
+const MAX_LATEST_MAX_AGE_MINUTES: i64 = 24 * 60;
+
+#[derive(Deserialize)]
+pub struct FindLatestQueryParams {
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+	include_invalid: Option<bool>,
+	// Leaves out locations whose latest reading is older, e.g. stations that went silent
+	max_age_minutes: Option<i64>,
+}
+
+#[get("/measurements/latest")]
+pub async fn find_latest(
+	state: web::Data<AppState>,
+	query: web::Query<FindLatestQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let bbox = parse_bbox(&query.bbox)?;
+	let include_invalid = query.include_invalid.unwrap_or(false);
+	let max_age_minutes = query.max_age_minutes.unwrap_or(DEFAULT_LATEST_MAX_AGE_MINUTES);
+
+	if !(1..=MAX_LATEST_MAX_AGE_MINUTES).contains(&max_age_minutes) {
+		return Err(bad_request("INVALID_MAX_AGE", format!("'max_age_minutes' must be between 1 and {}", MAX_LATEST_MAX_AGE_MINUTES)));
+	}
+
+	let measurements = CurrentTrafficMeasurement::get_all(&state.pool, FindCurrentMeasurementsParams {
+		bbox,
+		include_invalid,
+		since: Some(Utc::now() - Duration::minutes(max_age_minutes)),
+		min_congestion_level: None,
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(measurements))
+}
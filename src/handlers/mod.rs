@@ -0,0 +1,14 @@
+pub mod measurements;
+pub mod configuration;
+pub mod locations;
+pub mod aggregates;
+pub mod profiles;
+pub mod congestion;
+pub mod anomalies;
+pub mod sites;
+pub mod roads;
+pub mod queues;
+pub mod validation;
+pub mod reports;
+pub mod emissions;
+pub mod sensors;
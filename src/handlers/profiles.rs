@@ -0,0 +1,44 @@
+use actix_web::{get, web, HttpResponse, Result};
+use serde::Deserialize;
+
+use crate::{dto::profile::TrafficProfileDTO, errors::AppError, models::traffic_profile::TrafficProfile, state::AppState};
+
+use super::{measurements::FindByLocationIdPathParams, validation::{bad_request, not_found, parse_location_id}};
+
+#[derive(Deserialize)]
+pub struct FindProfileQueryParams {
+	// ISO weekday, 1 is monday
+	weekday: Option<i16>,
+}
+
+#[get("/locations/{location_id}/profile")]
+pub async fn find_by_location_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindByLocationIdPathParams>,
+	query: web::Query<FindProfileQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let profile_not_found = || not_found("PROFILE_NOT_FOUND", format!("No traffic profile for location {}", params.location_id));
+
+	if query.weekday.is_some_and(|weekday| !(1..=7).contains(&weekday)) {
+		return Err(bad_request("INVALID_WEEKDAY", "'weekday' must be between 1 (monday) and 7 (sunday)".to_owned()));
+	}
+
+	let location_id = parse_location_id(&params.location_id)?;
+
+	let refresh = TrafficProfile::get_latest_refresh(&state.pool)
+		.await?
+		.ok_or_else(profile_not_found)?;
+
+	let slots = TrafficProfile::get_by_location_id(&state.pool, location_id, query.weekday)
+		.await?;
+
+	if slots.is_empty() {
+		return Err(profile_not_found());
+	}
+
+	Ok(HttpResponse::Ok().json(TrafficProfileDTO {
+		location_id,
+		refresh,
+		slots,
+	}))
+}
@@ -0,0 +1,81 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Deserialize;
+
+use crate::{dto::queue::QueueEventDetailDTO, errors::AppError, models::{queue::QueueEvent, site::RoadDirection}, state::AppState};
+
+use super::validation::{not_found, validate_bounded_time_range};
+
+const DEFAULT_HISTORY_RANGE_DAYS: i64 = 1;
+const MAX_HISTORY_RANGE_DAYS: i64 = 31;
+
+#[derive(Deserialize)]
+pub struct FindQueuesQueryParams {
+	// Road as parsed from Ident_8, e.g. A14
+	road: Option<String>,
+	direction: Option<RoadDirection>,
+}
+
+#[get("/queues")]
+pub async fn find_current(
+	state: web::Data<AppState>,
+	query: web::Query<FindQueuesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let queues = QueueEvent::get_current(&state.pool, query.road.as_ref().map(|road| road.to_uppercase()), query.direction)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(queues))
+}
+
+#[derive(Deserialize)]
+pub struct FindQueueHistoryQueryParams {
+	road: Option<String>,
+	direction: Option<RoadDirection>,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/queues/history")]
+pub async fn find_history(
+	state: web::Data<AppState>,
+	query: web::Query<FindQueueHistoryQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::days(DEFAULT_HISTORY_RANGE_DAYS),
+		Duration::days(MAX_HISTORY_RANGE_DAYS),
+	)?;
+
+	let queues = QueueEvent::get_history(&state.pool, query.road.as_ref().map(|road| road.to_uppercase()), query.direction, from, to)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(queues))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FindByQueueIdPathParams {
+	pub queue_id: String,
+}
+
+#[get("/queues/{queue_id}")]
+pub async fn find_by_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindByQueueIdPathParams>,
+) -> Result<HttpResponse, AppError> {
+	let queue_not_found = || not_found("QUEUE_NOT_FOUND", format!("Queue {} not found", params.queue_id));
+
+	let queue_id = params.queue_id.parse::<i32>()
+		.map_err(|_| queue_not_found())?;
+
+	let event = QueueEvent::get_by_id(&state.pool, queue_id)
+		.await?
+		.ok_or_else(queue_not_found)?;
+	let snapshots = QueueEvent::get_snapshots(&state.pool, queue_id)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(QueueEventDetailDTO {
+		event,
+		snapshots,
+	}))
+}
@@ -0,0 +1,53 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{Duration, NaiveDate};
+use serde::Deserialize;
+
+use crate::{errors::AppError, models::{road_kpi::{FindRoadKpisParams, KpiPeriod, RoadKpi}, site::RoadDirection}, state::AppState};
+
+use super::validation::bad_request;
+
+const DEFAULT_REPORT_RANGE_DAYS: i64 = 30;
+const MAX_REPORT_RANGE_DAYS: i64 = 3 * 366;
+
+#[derive(Deserialize)]
+pub struct FindRoadKpisQueryParams {
+	road: Option<String>,
+	direction: Option<RoadDirection>,
+	// Calendar days, both inclusive
+	from: Option<NaiveDate>,
+	to: Option<NaiveDate>,
+	period: Option<KpiPeriod>,
+}
+
+#[get("/reports/roads")]
+pub async fn find_road_kpis(
+	state: web::Data<AppState>,
+	query: web::Query<FindRoadKpisQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	// Today is never complete, so the default range ends yesterday. The KPIs are
+	// bucketed by local day, the same day the KPI job takes as today.
+	let to = match query.to {
+		Some(to) => to,
+		None => RoadKpi::local_today(&state.pool, &state.time_zone).await? - Duration::days(1),
+	};
+	let from = query.from.unwrap_or(to - Duration::days(DEFAULT_REPORT_RANGE_DAYS - 1));
+
+	if from > to {
+		return Err(bad_request("INVALID_RANGE", "'from' can not be after 'to'".to_owned()));
+	}
+
+	if to - from >= Duration::days(MAX_REPORT_RANGE_DAYS) {
+		return Err(bad_request("INVALID_RANGE", format!("The report range can not exceed {} days", MAX_REPORT_RANGE_DAYS)));
+	}
+
+	let kpis = RoadKpi::get_all(&state.pool, FindRoadKpisParams {
+		road: query.road.as_ref().map(|road| road.to_uppercase()),
+		direction: query.direction,
+		from,
+		to,
+		period: query.period.unwrap_or(KpiPeriod::Day),
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(kpis))
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, Duration, DurationRound, FixedOffset, Utc};
+use serde::Deserialize;
+
+use crate::{dto::{site::{ContourSiteDTO, SpeedContourDTO}, travel_time::RouteTravelTimeDTO}, errors::AppError, models::{current_traffic_measurement::MAX_READING_AGE_MINUTES, site::{RoadDirection, Site}, travel_time::SegmentTravelTime}, state::AppState};
+
+use super::validation::{bad_request, validate_bounded_time_range};
+
+const DEFAULT_CONTOUR_RANGE_HOURS: i64 = 2;
+const MAX_CONTOUR_RANGE_HOURS: i64 = 24;
+const DEFAULT_BUCKET_MINUTES: i32 = 5;
+const DEFAULT_TRAVEL_TIME_HISTORY_HOURS: i64 = 24;
+const MAX_TRAVEL_TIME_HISTORY_DAYS: i64 = 7;
+// Bucket sizes that divide an hour, so buckets line up with the clock
+const BUCKET_MINUTES: [i32; 9] = [1, 2, 3, 5, 10, 15, 20, 30, 60];
+
+#[derive(Deserialize, Debug)]
+pub struct FindByRoadPathParams {
+	// Road as parsed from Ident_8, e.g. A14
+	pub road: String,
+}
+
+#[derive(Deserialize)]
+pub struct FindCorridorQueryParams {
+	direction: RoadDirection,
+}
+
+#[get("/roads/{road}/corridor")]
+pub async fn find_corridor(
+	state: web::Data<AppState>,
+	params: web::Path<FindByRoadPathParams>,
+	query: web::Query<FindCorridorQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let since = Utc::now() - Duration::minutes(MAX_READING_AGE_MINUTES);
+
+	let sites = Site::get_corridor(&state.pool, &params.road.to_uppercase(), query.direction, since)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(sites))
+}
+
+#[derive(Deserialize)]
+pub struct FindContourQueryParams {
+	direction: RoadDirection,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+	bucket_minutes: Option<i32>,
+}
+
+#[get("/roads/{road}/contour")]
+pub async fn find_contour(
+	state: web::Data<AppState>,
+	params: web::Path<FindByRoadPathParams>,
+	query: web::Query<FindContourQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let road = params.road.to_uppercase();
+	let bucket_minutes = query.bucket_minutes.unwrap_or(DEFAULT_BUCKET_MINUTES);
+
+	if !BUCKET_MINUTES.contains(&bucket_minutes) {
+		return Err(bad_request("INVALID_BUCKET", format!("'bucket_minutes' must be one of {:?}", BUCKET_MINUTES)));
+	}
+
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::hours(DEFAULT_CONTOUR_RANGE_HOURS),
+		Duration::hours(MAX_CONTOUR_RANGE_HOURS),
+	)?;
+
+	let since = Utc::now() - Duration::minutes(MAX_READING_AGE_MINUTES);
+	let sites: Vec<ContourSiteDTO> = Site::get_corridor(&state.pool, &road, query.direction, since)
+		.await?
+		.into_iter()
+		.map(|site| ContourSiteDTO {
+			site_id: site.site_id,
+			full_name: site.full_name,
+			kilometer_point: Some(site.kilometer_point),
+		})
+		.collect();
+
+	let speeds: HashMap<(String, DateTime<Utc>), Option<f64>> = Site::get_speed_contour(&state.pool, &road, query.direction, from, to, bucket_minutes)
+		.await?
+		.into_iter()
+		.map(|cell| ((cell.site_id, cell.bucket), cell.space_mean_speed))
+		.collect();
+
+	// Every bucket gets a row, also when none of the sites reported
+	let bucket = Duration::minutes(bucket_minutes as i64);
+	let mut times = vec![];
+	let mut time = from.duration_trunc(bucket).expect("bucket truncation");
+	while time < to {
+		times.push(time);
+		time += bucket;
+	}
+
+	let matrix = times
+		.iter()
+		.map(|time| {
+			sites
+				.iter()
+				.map(|site| speeds.get(&(site.site_id.clone(), *time)).copied().flatten())
+				.collect()
+		})
+		.collect();
+
+	Ok(HttpResponse::Ok().json(SpeedContourDTO {
+		road,
+		direction: query.direction,
+		from,
+		to,
+		bucket_minutes,
+		sites,
+		times,
+		speeds: matrix,
+	}))
+}
+
+fn validate_route(from_km: f64, to_km: f64) -> Result<(), AppError> {
+	if !from_km.is_finite() || !to_km.is_finite() || from_km == to_km {
+		return Err(bad_request("INVALID_ROUTE", "'from_km' and 'to_km' must be two different kilometer points".to_owned()));
+	}
+
+	Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct FindTravelTimeQueryParams {
+	direction: RoadDirection,
+	from_km: f64,
+	to_km: f64,
+	// Defaults to now
+	at: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/roads/{road}/travel-time")]
+pub async fn find_travel_time(
+	state: web::Data<AppState>,
+	params: web::Path<FindByRoadPathParams>,
+	query: web::Query<FindTravelTimeQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	validate_route(query.from_km, query.to_km)?;
+
+	let road = params.road.to_uppercase();
+	let at = query.at
+		.map(|at| at.with_timezone(&Utc))
+		.unwrap_or_else(Utc::now);
+
+	let segments = SegmentTravelTime::get_route_segments(&state.pool, &road, query.direction, query.from_km, query.to_km, at)
+		.await?;
+
+	let covered_km = segments.iter().map(|segment| segment.route_km).sum();
+	let complete = SegmentTravelTime::is_complete(covered_km, query.from_km, query.to_km);
+	// A partial sum would look like a fast route, so gaps leave it unknown
+	let travel_time_seconds = complete.then(|| segments
+		.iter()
+		.map(|segment| segment.travel_time_seconds * segment.route_km / segment.length_km)
+		.sum());
+
+	Ok(HttpResponse::Ok().json(RouteTravelTimeDTO {
+		road,
+		direction: query.direction,
+		from_km: query.from_km,
+		to_km: query.to_km,
+		at,
+		travel_time_seconds,
+		covered_km,
+		complete,
+		segments,
+	}))
+}
+
+#[derive(Deserialize)]
+pub struct FindTravelTimeHistoryQueryParams {
+	direction: RoadDirection,
+	from_km: f64,
+	to_km: f64,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/roads/{road}/travel-time/history")]
+pub async fn find_travel_time_history(
+	state: web::Data<AppState>,
+	params: web::Path<FindByRoadPathParams>,
+	query: web::Query<FindTravelTimeHistoryQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	validate_route(query.from_km, query.to_km)?;
+
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::hours(DEFAULT_TRAVEL_TIME_HISTORY_HOURS),
+		Duration::days(MAX_TRAVEL_TIME_HISTORY_DAYS),
+	)?;
+
+	let history = SegmentTravelTime::get_route_history(&state.pool, &params.road.to_uppercase(), query.direction, query.from_km, query.to_km, from, to)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(history))
+}
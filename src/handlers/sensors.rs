@@ -0,0 +1,92 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Deserialize;
+
+use crate::{dto::sensor_health::{SensorHealthDTO, SensorHealthSummaryDTO}, errors::AppError, models::sensor_health::{EquipmentOutage, FindSensorHealthParams, SensorHealth, SensorStatus, HEALTH_WINDOW_HOURS}, state::AppState};
+
+use super::validation::{parse_bbox, validate_bounded_time_range};
+
+const DEFAULT_OUTAGE_RANGE_DAYS: i64 = 7;
+const MAX_OUTAGE_RANGE_DAYS: i64 = 366;
+
+#[derive(Deserialize)]
+pub struct FindSensorHealthQueryParams {
+	// Only list locations and stations with this status, the summary covers all of them
+	status: Option<SensorStatus>,
+	// lve_nr
+	equipment_number: Option<i32>,
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+}
+
+#[get("/sensors/health")]
+pub async fn find_health(
+	state: web::Data<AppState>,
+	query: web::Query<FindSensorHealthQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let bbox = parse_bbox(&query.bbox)?;
+
+	let reference_time = SensorHealth::get_reference_time(&state.pool)
+		.await?;
+	let locations = SensorHealth::get_locations(&state.pool, FindSensorHealthParams {
+		equipment_number: query.equipment_number,
+		bbox,
+	}, reference_time)
+		.await?;
+	let open_outages = EquipmentOutage::get_open(&state.pool)
+		.await?;
+	let equipment = SensorHealth::group_by_equipment(&locations, &open_outages);
+
+	let count_status = |status| locations.iter().filter(|location| location.status == status).count();
+
+	let summary = SensorHealthSummaryDTO {
+		reference_time,
+		window_hours: HEALTH_WINDOW_HOURS,
+		location_count: locations.len(),
+		ok_count: count_status(SensorStatus::Ok),
+		degraded_count: count_status(SensorStatus::Degraded),
+		silent_count: count_status(SensorStatus::Silent),
+		equipment_count: equipment.len(),
+		equipment_outage_count: equipment.iter().filter(|equipment| equipment.outage_since.is_some()).count(),
+	};
+
+	let health = match query.status {
+		Some(status) => SensorHealthDTO {
+			summary,
+			locations: locations.into_iter().filter(|location| location.status == status).collect(),
+			equipment: equipment.into_iter().filter(|equipment| equipment.status == status).collect(),
+		},
+		None => SensorHealthDTO {
+			summary,
+			locations,
+			equipment,
+		},
+	};
+
+	Ok(HttpResponse::Ok().json(health))
+}
+
+#[derive(Deserialize)]
+pub struct FindOutagesQueryParams {
+	equipment_number: Option<i32>,
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/sensors/outages")]
+pub async fn find_outages(
+	state: web::Data<AppState>,
+	query: web::Query<FindOutagesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::days(DEFAULT_OUTAGE_RANGE_DAYS),
+		Duration::days(MAX_OUTAGE_RANGE_DAYS),
+	)?;
+
+	let outages = EquipmentOutage::get_history(&state.pool, query.equipment_number, from, to)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(outages))
+}
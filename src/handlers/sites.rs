@@ -0,0 +1,86 @@
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Deserialize;
+
+use crate::{errors::AppError, models::site::{FindSitesParams, RoadDirection, Site}, state::AppState};
+
+use super::validation::{not_found, parse_bbox, validate_bounded_time_range};
+
+const DEFAULT_MEASUREMENTS_RANGE_HOURS: i64 = 1;
+const MAX_MEASUREMENTS_RANGE_DAYS: i64 = 7;
+
+#[derive(Deserialize)]
+pub struct FindSitesQueryParams {
+	// Road as parsed from Ident_8, e.g. A14
+	road: Option<String>,
+	direction: Option<RoadDirection>,
+	// minLon,minLat,maxLon,maxLat in EPSG:4326
+	bbox: Option<String>,
+}
+
+#[get("/sites")]
+pub async fn find_all(
+	state: web::Data<AppState>,
+	query: web::Query<FindSitesQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let bbox = parse_bbox(&query.bbox)?;
+
+	let sites = Site::get_all(&state.pool, FindSitesParams {
+		road: query.road.clone().filter(|road| !road.trim().is_empty()),
+		direction: query.direction,
+		bbox,
+	})
+		.await?;
+
+	Ok(HttpResponse::Ok().json(sites))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FindBySiteIdPathParams {
+	pub site_id: String,
+}
+
+fn site_not_found(site_id: &str) -> AppError {
+	not_found("SITE_NOT_FOUND", format!("Site {} not found", site_id))
+}
+
+#[get("/sites/{site_id}")]
+pub async fn find_by_id(
+	state: web::Data<AppState>,
+	params: web::Path<FindBySiteIdPathParams>,
+) -> Result<HttpResponse, AppError> {
+	let site = Site::get_by_id(&state.pool, &params.site_id)
+		.await?
+		.ok_or_else(|| site_not_found(&params.site_id))?;
+
+	Ok(HttpResponse::Ok().json(site))
+}
+
+#[derive(Deserialize)]
+pub struct FindSiteMeasurementsQueryParams {
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+}
+
+#[get("/sites/{site_id}/measurements")]
+pub async fn find_measurements(
+	state: web::Data<AppState>,
+	params: web::Path<FindBySiteIdPathParams>,
+	query: web::Query<FindSiteMeasurementsQueryParams>,
+) -> Result<HttpResponse, AppError> {
+	let (from, to) = validate_bounded_time_range(
+		query.from,
+		query.to,
+		Duration::hours(DEFAULT_MEASUREMENTS_RANGE_HOURS),
+		Duration::days(MAX_MEASUREMENTS_RANGE_DAYS),
+	)?;
+
+	let site = Site::get_by_id(&state.pool, &params.site_id)
+		.await?
+		.ok_or_else(|| site_not_found(&params.site_id))?;
+
+	let measurements = Site::get_measurements(&state.pool, &site.location_ids, from, to)
+		.await?;
+
+	Ok(HttpResponse::Ok().json(measurements))
+}
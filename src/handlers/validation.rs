@@ -0,0 +1,163 @@
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+
+use crate::{errors::{AppError, AppErrorValue}, models::location::BoundingBox};
+
+// Radius in meters
+const DEFAULT_RADIUS: f64 = 1000.0;
+const MAX_RADIUS: f64 = 100_000.0;
+const MAX_TIME_RANGE_DAYS: i64 = 31;
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 1000;
+
+pub fn bad_request(code: &str, message: String) -> AppError {
+	AppError::BadRequest(AppErrorValue {
+		message,
+		status: StatusCode::BAD_REQUEST.as_u16(),
+		code: code.to_owned(),
+		..Default::default()
+	})
+}
+
+pub fn not_found(code: &str, message: String) -> AppError {
+	AppError::NotFound(AppErrorValue {
+		message,
+		status: StatusCode::NOT_FOUND.as_u16(),
+		code: code.to_owned(),
+		..Default::default()
+	})
+}
+
+pub fn location_not_found(location_id: &str) -> AppError {
+	not_found("LOCATION_NOT_FOUND", format!("Location {} not found", location_id))
+}
+
+/// Parses a location id from the path, an id that is not a number can not exist
+pub fn parse_location_id(location_id: &str) -> Result<i32, AppError> {
+	location_id.parse::<i32>()
+		.map_err(|_| location_not_found(location_id))
+}
+
+/// Checks that a point lies within the EPSG:4326 latitude and longitude ranges
+pub fn validate_coordinates(lat: f64, lon: f64) -> Result<(), AppError> {
+	if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+		return Err(bad_request("INVALID_LOCATION", "'lat' must be between -90 and 90, 'lon' between -180 and 180".to_owned()));
+	}
+
+	Ok(())
+}
+
+/// Validates a center point and returns the radius in meters to use around it
+pub fn validate_location(lat: Option<f64>, lon: Option<f64>, radius: Option<f64>) -> Result<f64, AppError> {
+	let radius = radius.unwrap_or(DEFAULT_RADIUS);
+
+	if lat.is_some() != lon.is_some() {
+		return Err(bad_request("INVALID_LOCATION", "'lat' and 'lon' must be given together".to_owned()));
+	}
+
+	if let (Some(lat), Some(lon)) = (lat, lon) {
+		validate_coordinates(lat, lon)?;
+	}
+
+	if !(radius > 0.0 && radius <= MAX_RADIUS) {
+		return Err(bad_request("INVALID_RADIUS", format!("'radius' must be between 0 and {} meters", MAX_RADIUS)));
+	}
+
+	Ok(radius)
+}
+
+/// Parses a `minLon,minLat,maxLon,maxLat` bounding box in EPSG:4326
+pub fn parse_bbox(bbox: &Option<String>) -> Result<Option<BoundingBox>, AppError> {
+	let Some(bbox) = bbox else {
+		return Ok(None);
+	};
+
+	let invalid = || bad_request("INVALID_BBOX", "'bbox' must be formatted as minLon,minLat,maxLon,maxLat".to_owned());
+
+	let coordinates = bbox
+		.split(',')
+		.map(|coordinate| coordinate.trim().parse::<f64>())
+		.collect::<Result<Vec<f64>, _>>()
+		.map_err(|_| invalid())?;
+
+	let [min_lon, min_lat, max_lon, max_lat] = coordinates[..] else {
+		return Err(invalid());
+	};
+
+	if validate_coordinates(min_lat, min_lon).is_err() || validate_coordinates(max_lat, max_lon).is_err() {
+		return Err(bad_request("INVALID_BBOX", "'bbox' must lie within -180,-90,180,90".to_owned()));
+	}
+
+	if !(min_lon < max_lon && min_lat < max_lat) {
+		return Err(invalid());
+	}
+
+	Ok(Some(BoundingBox {
+		min_lon,
+		min_lat,
+		max_lon,
+		max_lat,
+	}))
+}
+
+pub type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Validates the requested time range. An open ended range starting at `from`
+/// ends now, a range with only `to` starts the maximum range before it.
+pub fn validate_time_range(
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+) -> Result<TimeRange, AppError> {
+	let to = to.map(|to| to.with_timezone(&Utc));
+	let from = match (from, to) {
+		(Some(from), _) => Some(from.with_timezone(&Utc)),
+		(None, Some(to)) => Some(to - Duration::days(MAX_TIME_RANGE_DAYS)),
+		(None, None) => None,
+	};
+
+	if let Some(from) = from {
+		let to = to.unwrap_or_else(Utc::now);
+
+		if from >= to {
+			return Err(bad_request("INVALID_RANGE", "'from' must be before 'to'".to_owned()));
+		}
+
+		if to - from > Duration::days(MAX_TIME_RANGE_DAYS) {
+			return Err(bad_request("INVALID_RANGE", format!("The time range can not exceed {} days", MAX_TIME_RANGE_DAYS)));
+		}
+	}
+
+	Ok((from, to))
+}
+
+pub fn validate_limit(limit: Option<i64>) -> Result<i64, AppError> {
+	let limit = limit.unwrap_or(DEFAULT_LIMIT);
+
+	if !(1..=MAX_LIMIT).contains(&limit) {
+		return Err(bad_request("INVALID_LIMIT", format!("'limit' must be between 1 and {}", MAX_LIMIT)));
+	}
+
+	Ok(limit)
+}
+
+/// Resolves a closed time range for endpoints that always need both bounds.
+/// A missing `to` defaults to now, a missing `from` to `default_range` before `to`.
+pub fn validate_bounded_time_range(
+	from: Option<DateTime<FixedOffset>>,
+	to: Option<DateTime<FixedOffset>>,
+	default_range: Duration,
+	max_range: Duration,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), AppError> {
+	let to = to.map(|to| to.with_timezone(&Utc)).unwrap_or_else(Utc::now);
+	let from = from.map(|from| from.with_timezone(&Utc)).unwrap_or(to - default_range);
+
+	if from >= to {
+		return Err(bad_request("INVALID_RANGE", "'from' must be before 'to'".to_owned()));
+	}
+
+	if to - from > max_range {
+		return Err(bad_request("INVALID_RANGE", format!("The time range can not exceed {} days", max_range.num_days())));
+	}
+
+	Ok((from, to))
+}
@@ -3,71 +3,22 @@ pub mod tasks;
 pub mod models;
 pub mod state;
 pub mod dto;
+pub mod handlers;
 
 use std::env;
 
-use actix_web::{get, web, App, HttpResponse, HttpServer, Result};
+use actix_web::{App, HttpServer, Result};
 use chrono::{DateTime, FixedOffset};
 use dotenv::dotenv;
 use errors::AppError;
-use models::traffic_measurement::{FindMeasurementsByLocationIdParams, FindMeasurementsParams, TrafficMeasurement, VehicleClass};
+use handlers::{aggregates, anomalies, configuration, congestion, emissions, locations, measurements, profiles, queues, reports, roads, sensors, sites};
+use models::traffic_measurement::VehicleClass;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPoolOptions;
 use state::AppState;
-use tasks::seed_traffic_data::seed_traffic_data;
+use tasks::{apply_storage_policies::apply_storage_policies, backfill_aggregates::backfill_aggregates, load_emission_factors::load_emission_factors, refresh_free_flow_speeds::{free_flow_window_days, refresh_free_flow_speeds}, refresh_road_kpis::{refresh_road_kpis, road_kpi_days}, refresh_traffic_profiles::{profile_time_zone, profile_window_days, refresh_traffic_profiles}, seed_traffic_data::{anomaly_min_severity, plausibility_rules, seed_traffic_data}};
 use tokio_cron_scheduler::{Job, JobScheduler};
 
-#[derive(Deserialize)]
-pub struct FindAllQueryParams {
-	lat: Option<f64>,
-	lon: Option<f64>,
-	radius: Option<f64>,
-	limit: Option<i64>
-}
-
-#[get("/measurements")]
-pub async fn find_all(
-	state: web::Data<AppState>,
-	query: web::Query<FindAllQueryParams>,
-) -> Result<HttpResponse, AppError> {
-	let lat = query.lat;
-	let lon = query.lon;
-	let radius = query.radius.unwrap_or(1000.0);
-	let limit = query.limit.unwrap_or(20);
-
-	let measurements = TrafficMeasurement::get_recent(&state.pool, FindMeasurementsParams {
-		lat,
-		lon,
-		limit,
-		radius
-	})
-		.await?;
-
-	Ok(HttpResponse::Ok().json(measurements))
-}
-
-#[derive(Deserialize, Debug)]
-pub struct FindByLocationIdPathParams {
-	pub location_id: String,
-}
-
-#[get("/locations/{location_id}/measurements")]
-pub async fn find_by_location_id(
-	state: web::Data<AppState>,
-	query: web::Query<FindAllQueryParams>,
-	params: web::Path<FindByLocationIdPathParams>,
-) -> Result<HttpResponse, AppError> {
-	let limit = query.limit.unwrap_or(20);
-
-	let measurements = TrafficMeasurement::get_by_location_id(&state.pool, params.location_id.clone(), FindMeasurementsByLocationIdParams {
-		limit
-	})
-		.await?;
-
-	Ok(HttpResponse::Ok().json(measurements))
-}
-
-
 fn deserialize_vehicle_class<'de, D>(deserializer: D) -> Result<VehicleClass, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -96,6 +47,22 @@ where
     Ok(coordinate_as_f64)
 }
 
+fn deserialize_optional_dutch_decimal<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    // Empty elements (e.g. `<Kmp_Rsys></Kmp_Rsys>`) and "NULL" are mapped to None
+    let value = Option::<String>::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") | Some("NULL") => Ok(None),
+        Some(value) => value
+            .replace(",", ".")
+            .parse::<f64>()
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
 /// Top level structure (MIV = Measuring Instruments for Traffic)
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename = "miv")]
@@ -148,7 +115,7 @@ pub struct MeasuringPoint {
     #[serde(rename = "defect")]
     pub faulty: i32,
 
-    // geldig -> valid
+    // geldig -> valid, 0 for a valid reading
     #[serde(rename = "geldig")]
     pub valid: i32,
 
@@ -210,6 +177,39 @@ pub struct TrafficDataLocations {
 pub struct MeasuringPointLocation {
     #[serde(rename = "@unieke_id")]
     pub unique_id: i32,
+
+    // beschrijvende_id -> descriptive_id
+    #[serde(rename = "beschrijvende_id")]
+    pub descriptive_id: String,
+
+    // volledige_naam -> full_name
+    #[serde(rename = "volledige_naam")]
+    pub full_name: String,
+
+    // Ident_8 -> ident_8
+    #[serde(rename = "Ident_8")]
+    pub ident_8: String,
+
+    // lve_nr -> equipment_number
+    #[serde(rename = "lve_nr")]
+    pub equipment_number: i32,
+
+    // Kmp_Rsys -> kilometer_point
+    #[serde(rename = "Kmp_Rsys", default, deserialize_with = "deserialize_optional_dutch_decimal")]
+    pub kilometer_point: Option<f64>,
+
+    // Rijstrook -> lane
+    #[serde(rename = "Rijstrook")]
+    pub lane: String,
+
+    // X_coord_EPSG_31370 -> lambert_x
+    #[serde(rename = "X_coord_EPSG_31370", deserialize_with = "deserialize_dutch_coordinate")]
+    pub lambert_x: f64,
+
+    // Y_coord_EPSG_31370 -> lambert_y
+    #[serde(rename = "Y_coord_EPSG_31370", deserialize_with = "deserialize_dutch_coordinate")]
+    pub lambert_y: f64,
+
     #[serde(rename = "breedtegraad_EPSG_4326", deserialize_with = "deserialize_dutch_coordinate")]
 	pub latitude: f64,
     #[serde(rename = "lengtegraad_EPSG_4326", deserialize_with = "deserialize_dutch_coordinate")]
@@ -226,17 +226,69 @@ async fn main() -> std::result::Result<(), AppError> {
         .connect(&env::var("DATABASE_URL").expect("DATABASE_URL missing"))
         .await?;
 
-    // Add basic cron job
-    scheduler.add(
+	apply_storage_policies(&pool).await?;
+	load_emission_factors(&pool).await?;
+
+	// Refuse to start with settings the scheduled jobs would reject
+	let time_zone = profile_time_zone(&pool).await?;
+	profile_window_days()?;
+	free_flow_window_days()?;
+	anomaly_min_severity()?;
+	plausibility_rules()?;
+	road_kpi_days()?;
+
+	// Materialize the history the aggregate refresh policies do not reach, in the
+	// background since a first run over months of raw data takes a while
+	let backfill_pool = pool.clone();
+	actix_web::rt::spawn(async move {
+		if let Err(err) = backfill_aggregates(&backfill_pool).await {
+			println!("Backfilling the aggregates failed: {:?}", err);
+		}
+	});
+
+	// Ingest the feed every minute, a failed run is retried by the next one
+	scheduler.add(
 		Job::new_async("0 * * * * *", |_uuid, _l| {
-			println!("yolo");
-            Box::pin(async move {
-				seed_traffic_data()
-					.await
-					.unwrap()
+			Box::pin(async move {
+				if let Err(err) = seed_traffic_data().await {
+					println!("Ingesting traffic data failed: {:?}", err);
+				}
+			})
+		})?
+	).await?;
+
+	// Rebuild the typical-day profiles every night
+	scheduler.add(
+		Job::new_async("0 30 3 * * *", |_uuid, _l| {
+			Box::pin(async move {
+				if let Err(err) = refresh_traffic_profiles().await {
+					println!("Refreshing traffic profiles failed: {:?}", err);
+				}
+			})
+		})?
+	).await?;
+
+	// Relearn the free-flow speeds once the night has been aggregated
+	scheduler.add(
+		Job::new_async("0 45 3 * * *", |_uuid, _l| {
+			Box::pin(async move {
+				if let Err(err) = refresh_free_flow_speeds().await {
+					println!("Refreshing free-flow speeds failed: {:?}", err);
+				}
+			})
+		})?
+	).await?;
+
+	// Report the previous days per road after the hourly aggregate caught up
+	scheduler.add(
+		Job::new_async("0 15 4 * * *", |_uuid, _l| {
+			Box::pin(async move {
+				if let Err(err) = refresh_road_kpis().await {
+					println!("Refreshing road KPIs failed: {:?}", err);
+				}
 			})
-        })?
-    ).await?;
+		})?
+	).await?;
 
     scheduler.start().await?;
 
@@ -253,12 +305,40 @@ async fn main() -> std::result::Result<(), AppError> {
 			.connect(&env::var("DATABASE_URL").expect("DATABASE_URL missing"))
 			.await?;
 
-		AppState { pool }
+		AppState { pool, time_zone }
 	};
 
     let _ = HttpServer::new(move || App::new()
-		.service(find_all)
-		.service(find_by_location_id)
+		.service(measurements::find_all)
+		.service(measurements::find_by_location_id)
+		.service(measurements::find_latest)
+		.service(measurements::find_classes_by_location_id)
+		.service(locations::find_all)
+		.service(locations::find_nearest)
+		.service(locations::find_by_id)
+		.service(aggregates::find_by_location_id)
+		.service(aggregates::find_series_by_location_id)
+		.service(profiles::find_by_location_id)
+		.service(emissions::find_by_location_id)
+		.service(emissions::find_by_area)
+		.service(congestion::find_all)
+		.service(anomalies::find_all)
+		.service(sites::find_all)
+		.service(sites::find_by_id)
+		.service(sites::find_measurements)
+		.service(roads::find_corridor)
+		.service(roads::find_contour)
+		.service(roads::find_travel_time)
+		.service(roads::find_travel_time_history)
+		.service(queues::find_current)
+		.service(queues::find_history)
+		.service(queues::find_by_id)
+		.service(reports::find_road_kpis)
+		.service(sensors::find_health)
+		.service(sensors::find_outages)
+		.service(configuration::find_versions)
+		.service(configuration::find_locations)
+		.service(configuration::find_changes)
 		.app_data(actix_web::web::Data::new(state.clone()))
 	)
         .bind(("0.0.0.0", 8080))?
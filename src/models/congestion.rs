@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Level of service of a reading compared to the free-flow speed of its location,
+/// see the `classify_congestion` database function for the thresholds. Readings are
+/// compared on their time-mean speed, the metric the free-flow speed is learned from.
+#[derive(sqlx::Type, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
+#[sqlx(type_name = "congestion_level", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CongestionLevel {
+	FreeFlow,
+	Dense,
+	Slow,
+	Stationary,
+}
+
+// Night-time readings used to learn the free-flow speed, in local time
+const NIGHT_START_HOUR: i32 = 0;
+const NIGHT_END_HOUR: i32 = 5;
+// Buckets with more occupancy than this are not free flowing
+const MAX_NIGHT_OCCUPANCY: f64 = 10.0;
+const MIN_BUCKET_SAMPLES: i64 = 10;
+// Locations with fewer night buckets keep their previous baseline
+const MIN_NIGHT_BUCKETS: i64 = 20;
+
+pub struct FreeFlowSpeed;
+
+impl FreeFlowSpeed {
+	/// Learns the free-flow speed of every location as the median speed of the quiet
+	/// night-time 15 minute buckets between `window_start` and `window_end`.
+	/// Returns the number of locations that got a new baseline.
+	pub async fn refresh(
+		pool: &sqlx::PgPool,
+		window_start: DateTime<Utc>,
+		window_end: DateTime<Utc>,
+		time_zone: &str,
+	) -> Result<u64, sqlx::Error> {
+		let result = sqlx::query!(
+			r#"
+			INSERT INTO public.free_flow_speeds (
+				location_id,
+				free_flow_speed,
+				sample_count,
+				computed_at
+			)
+			SELECT
+				location_id,
+				percentile_cont(0.5) WITHIN GROUP (ORDER BY speed),
+				COUNT(*),
+				NOW()
+			FROM (
+				SELECT
+					location_id,
+					time_mean_speed_sum / NULLIF(time_mean_vehicles, 0) AS speed
+				FROM public.traffic_measurements_15m
+				WHERE bucket >= $1 AND bucket < $2
+					AND sample_count >= $7
+					AND EXTRACT(HOUR FROM bucket AT TIME ZONE $3)::INTEGER >= $4
+					AND EXTRACT(HOUR FROM bucket AT TIME ZONE $3)::INTEGER < $5
+					AND occupancy_rate_sum::DOUBLE PRECISION / NULLIF(occupancy_rate_count, 0) <= $6
+			) night
+			WHERE speed IS NOT NULL
+			GROUP BY location_id
+			HAVING COUNT(*) >= $8
+			ON CONFLICT (location_id) DO UPDATE SET
+				free_flow_speed = EXCLUDED.free_flow_speed,
+				sample_count = EXCLUDED.sample_count,
+				computed_at = EXCLUDED.computed_at
+			"#,
+			window_start,
+			window_end,
+			time_zone,
+			NIGHT_START_HOUR,
+			NIGHT_END_HOUR,
+			MAX_NIGHT_OCCUPANCY,
+			MIN_BUCKET_SAMPLES,
+			MIN_NIGHT_BUCKETS
+		)
+		.execute(pool)
+		.await?;
+
+		Ok(result.rows_affected())
+	}
+}
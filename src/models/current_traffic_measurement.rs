@@ -0,0 +1,194 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::dto::measurement::MeasurementDTO;
+
+use super::{congestion::CongestionLevel, location::BoundingBox, traffic_measurement::VALID_MEASUREMENTS_FILTER};
+
+// Readings older than this no longer describe the current situation
+pub const MAX_READING_AGE_MINUTES: i64 = 15;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindCurrentMeasurementsParams {
+	pub bbox: Option<BoundingBox>,
+	pub include_invalid: bool,
+	// Leaves out locations that have not reported since
+	pub since: Option<DateTime<Utc>>,
+	pub min_congestion_level: Option<CongestionLevel>,
+}
+
+/// The latest measurement of every location
+pub struct CurrentTrafficMeasurement;
+
+impl CurrentTrafficMeasurement {
+	/// Moves the current state forward with the measurements observed since `since`
+	pub async fn refresh_since(
+		pool: &sqlx::PgPool,
+		since: DateTime<Utc>,
+	) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"
+			INSERT INTO public.current_traffic_measurements (
+				location_id,
+				observation_time,
+				availability_rate,
+				occupancy_rate,
+				instability,
+				total_vehicles_passed,
+				average_speed,
+				max_speed,
+				time_mean_speed,
+				space_mean_speed,
+				data_quality,
+				current_publication,
+				available,
+				faulty,
+				valid,
+				plausibility_flags
+			)
+			SELECT DISTINCT ON (location_id)
+				location_id,
+				observation_time,
+				availability_rate,
+				occupancy_rate,
+				instability,
+				total_vehicles_passed,
+				average_speed,
+				max_speed,
+				time_mean_speed,
+				space_mean_speed,
+				data_quality,
+				current_publication,
+				available,
+				faulty,
+				valid,
+				plausibility_flags
+			FROM public.traffic_measurements
+			WHERE observation_time >= $1
+			ORDER BY location_id, observation_time DESC
+			ON CONFLICT (location_id) DO UPDATE SET
+				observation_time = EXCLUDED.observation_time,
+				availability_rate = EXCLUDED.availability_rate,
+				occupancy_rate = EXCLUDED.occupancy_rate,
+				instability = EXCLUDED.instability,
+				total_vehicles_passed = EXCLUDED.total_vehicles_passed,
+				average_speed = EXCLUDED.average_speed,
+				max_speed = EXCLUDED.max_speed,
+				time_mean_speed = EXCLUDED.time_mean_speed,
+				space_mean_speed = EXCLUDED.space_mean_speed,
+				data_quality = EXCLUDED.data_quality,
+				current_publication = EXCLUDED.current_publication,
+				available = EXCLUDED.available,
+				faulty = EXCLUDED.faulty,
+				valid = EXCLUDED.valid,
+				plausibility_flags = EXCLUDED.plausibility_flags
+			WHERE current_traffic_measurements.observation_time < EXCLUDED.observation_time
+			"#,
+			since
+		)
+		.execute(pool)
+		.await?;
+
+		Ok(())
+	}
+
+	/// Time of the most recent stored observation, optionally for a single location
+	pub async fn get_latest_observation_time(
+		pool: &sqlx::PgPool,
+		location_id: Option<i32>,
+	) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+		sqlx::query_scalar!(
+			r#"
+			SELECT MAX(observation_time)
+			FROM public.current_traffic_measurements
+			WHERE $1::INTEGER IS NULL OR location_id = $1
+			"#,
+			location_id
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	pub async fn get_all(
+		pool: &sqlx::PgPool,
+		params: FindCurrentMeasurementsParams,
+	) -> Result<Vec<MeasurementDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(
+			"SELECT
+				t.location_id,
+				t.observation_time,
+				t.occupancy_rate,
+				t.availability_rate,
+				t.instability,
+				t.total_vehicles_passed,
+				t.average_speed,
+				t.max_speed,
+				t.time_mean_speed,
+				t.space_mean_speed,
+				t.data_quality,
+				t.current_publication,
+				t.available,
+				t.faulty,
+				t.valid,
+				t.plausibility_flags,
+				f.free_flow_speed,
+				classify_congestion(
+					COALESCE(t.time_mean_speed, t.average_speed),
+					t.occupancy_rate,
+					t.total_vehicles_passed,
+					f.free_flow_speed
+				) AS congestion_level,
+				l.descriptive_id,
+				l.full_name,
+				l.ident_8,
+				l.equipment_number,
+				l.kilometer_point,
+				l.lane,
+				l.lambert_x,
+				l.lambert_y,
+				l.latitude,
+				l.longitude
+			FROM public.current_traffic_measurements t
+			INNER JOIN public.locations l ON t.location_id = l.location_id
+			LEFT JOIN public.free_flow_speeds f ON t.location_id = f.location_id
+			WHERE TRUE"
+		);
+
+		if let Some(since) = params.since {
+			query
+				.push(" AND t.observation_time >= ")
+				.push_bind(since);
+		}
+
+		if let Some(min_congestion_level) = params.min_congestion_level {
+			query
+				.push(" AND classify_congestion(COALESCE(t.time_mean_speed, t.average_speed), t.occupancy_rate, t.total_vehicles_passed, f.free_flow_speed) >= ")
+				.push_bind(min_congestion_level);
+		}
+
+		if let Some(bbox) = params.bbox {
+			query
+				.push(" AND l.geog && ST_MakeEnvelope(")
+				.push_bind(bbox.min_lon)
+				.push(", ")
+				.push_bind(bbox.min_lat)
+				.push(", ")
+				.push_bind(bbox.max_lon)
+				.push(", ")
+				.push_bind(bbox.max_lat)
+				.push(", 4326)::geography");
+		}
+
+		if !params.include_invalid {
+			query.push(VALID_MEASUREMENTS_FILTER);
+		}
+
+		query.push(" ORDER BY t.location_id");
+
+		query
+			.build_query_as::<MeasurementDTO>()
+			.fetch_all(pool)
+			.await
+	}
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::dto::emission::{EmissionDTO, LocationEmissionDTO};
+
+use super::{location::BoundingBox, traffic_measurement::{VehicleClass, VALID_MEASUREMENTS_FILTER}};
+
+/// Emission factor of a vehicle class within a speed band, in grams per vehicle-kilometre
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EmissionFactor {
+	pub vehicle_class: VehicleClass,
+	// km/h, inclusive
+	pub min_speed: f64,
+	// km/h, exclusive, open ended when missing
+	pub max_speed: Option<f64>,
+
+	pub co2: f64,
+	pub nox: f64,
+	pub pm: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindAreaEmissionsParams {
+	pub lat: Option<f64>,
+	pub lon: Option<f64>,
+	pub radius: f64,
+	pub bbox: Option<BoundingBox>,
+	pub from: DateTime<Utc>,
+	pub to: DateTime<Utc>,
+}
+
+// Per class intensities with the speed to pick the emission factor band by and the road
+// length the site of the lane stands for. Class readings without a valid speed fall back
+// to the free-flow speed of the location. Lanes outside a main carriageway site have no
+// represented length.
+const CLASS_SPEEDS: &str = "
+	SELECT
+		c.location_id,
+		c.observation_time,
+		c.vehicle_class,
+		c.traffic_intensity,
+		COALESCE(
+			CASE WHEN c.data_quality = 'VALID' THEN c.vehicle_speed_harmonic END,
+			f.free_flow_speed
+		) AS speed,
+		k.represented_km
+	FROM public.traffic_measurement_classes c
+	INNER JOIN public.traffic_measurements t
+		ON t.location_id = c.location_id AND t.observation_time = c.observation_time
+	LEFT JOIN public.free_flow_speeds f ON f.location_id = c.location_id
+	LEFT JOIN (
+		SELECT
+			UNNEST(ms.location_ids) AS location_id,
+			r.reach_before_km + r.reach_after_km AS represented_km
+		FROM public.measuring_sites ms
+		INNER JOIN public.measuring_site_reaches r ON r.site_id = ms.site_id
+	) k ON k.location_id = c.location_id
+	WHERE c.traffic_intensity > 0";
+
+// Grams emitted along the represented road length: the vehicles passing times their
+// emission factor in grams per vehicle-kilometre times the kilometres they stand for
+const EMISSION_TOTALS: &str = "
+	SUM(s.traffic_intensity)::BIGINT AS vehicles,
+	COALESCE(SUM(s.traffic_intensity) FILTER (WHERE e.vehicle_class IS NULL OR s.represented_km IS NULL), 0)::BIGINT AS unestimated_vehicles,
+	COALESCE(SUM(s.traffic_intensity * e.co2 * s.represented_km), 0) AS co2,
+	COALESCE(SUM(s.traffic_intensity * e.nox * s.represented_km), 0) AS nox,
+	COALESCE(SUM(s.traffic_intensity * e.pm * s.represented_km), 0) AS pm";
+
+const EMISSION_FACTOR_JOIN: &str = "
+	LEFT JOIN public.emission_factors e
+		ON e.vehicle_class = s.vehicle_class
+		AND s.speed >= e.min_speed
+		AND (e.max_speed IS NULL OR s.speed < e.max_speed)";
+
+impl EmissionFactor {
+	/// Replaces the stored emission factors with `factors`
+	pub async fn replace_all(
+		pool: &sqlx::PgPool,
+		factors: &[EmissionFactor],
+	) -> Result<(), sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		sqlx::query!("DELETE FROM public.emission_factors")
+			.execute(&mut *tx)
+			.await?;
+
+		for factor in factors {
+			sqlx::query!(
+				r#"
+				INSERT INTO public.emission_factors (vehicle_class, min_speed, max_speed, co2, nox, pm)
+				VALUES ($1, $2, $3, $4, $5, $6)
+				"#,
+				factor.vehicle_class as VehicleClass,
+				factor.min_speed,
+				factor.max_speed,
+				factor.co2,
+				factor.nox,
+				factor.pm
+			)
+			.execute(&mut *tx)
+			.await?;
+		}
+
+		tx.commit().await
+	}
+}
+
+pub struct Emission;
+
+impl Emission {
+	/// Hourly emission estimates of a single location, in grams per hour
+	pub async fn get_hourly_by_location_id(
+		pool: &sqlx::PgPool,
+		location_id: i32,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<EmissionDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new("WITH class_speeds AS (");
+		query
+			.push(CLASS_SPEEDS)
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(" AND c.location_id = ")
+			.push_bind(location_id)
+			.push(" AND c.observation_time >= ")
+			.push_bind(from)
+			.push(" AND c.observation_time < ")
+			.push_bind(to)
+			.push(
+				")
+				SELECT
+					time_bucket('1 hour', s.observation_time) AS hour,
+					MAX(s.represented_km) AS represented_km,"
+			)
+			.push(EMISSION_TOTALS)
+			.push(" FROM class_speeds s")
+			.push(EMISSION_FACTOR_JOIN)
+			.push(" GROUP BY 1 ORDER BY 1");
+
+		query
+			.build_query_as::<EmissionDTO>()
+			.fetch_all(pool)
+			.await
+	}
+
+	/// Emission estimates per location within an area, in grams totalled over the time
+	/// range. Each lane covers its own share of the road, so the locations add up.
+	pub async fn get_by_area(
+		pool: &sqlx::PgPool,
+		params: FindAreaEmissionsParams,
+	) -> Result<Vec<LocationEmissionDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new("WITH class_speeds AS (");
+		query
+			.push(CLASS_SPEEDS)
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(" AND c.observation_time >= ")
+			.push_bind(params.from)
+			.push(" AND c.observation_time < ")
+			.push_bind(params.to)
+			.push(")
+				SELECT
+					l.location_id,
+					l.descriptive_id,
+					l.full_name,
+					l.latitude,
+					l.longitude,
+					MAX(s.represented_km) AS represented_km,"
+			)
+			.push(EMISSION_TOTALS)
+			.push(
+				" FROM class_speeds s
+				INNER JOIN public.locations l ON l.location_id = s.location_id"
+			)
+			.push(EMISSION_FACTOR_JOIN)
+			.push(" WHERE TRUE");
+
+		if let (Some(lat), Some(lon)) = (params.lat, params.lon) {
+			query
+				.push(" AND ST_DWithin(l.geog, ST_SetSRID(ST_MakePoint(")
+				.push_bind(lon)
+				.push(", ")
+				.push_bind(lat)
+				.push("), 4326)::geography, ")
+				.push_bind(params.radius)
+				.push(")");
+		}
+
+		if let Some(bbox) = params.bbox {
+			query
+				.push(" AND l.geog && ST_MakeEnvelope(")
+				.push_bind(bbox.min_lon)
+				.push(", ")
+				.push_bind(bbox.min_lat)
+				.push(", ")
+				.push_bind(bbox.max_lon)
+				.push(", ")
+				.push_bind(bbox.max_lat)
+				.push(", 4326)::geography");
+		}
+
+		query.push(" GROUP BY l.location_id ORDER BY l.location_id");
+
+		query
+			.build_query_as::<LocationEmissionDTO>()
+			.fetch_all(pool)
+			.await
+	}
+}
@@ -1,12 +1,42 @@
 use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::dto::location::{LocationDTO, NearestLocationDTO};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Location {
     pub location_id: i32,
+    pub descriptive_id: String,
+    pub full_name: String,
+    pub ident_8: String,
+    pub equipment_number: i32,
+    pub kilometer_point: Option<f64>,
+    pub lane: String,
+    pub lambert_x: f64,
+    pub lambert_y: f64,
     pub latitude: f64,
     pub longitude: f64,
 }
 
+/// Bounding box in EPSG:4326
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BoundingBox {
+	pub min_lon: f64,
+	pub min_lat: f64,
+	pub max_lon: f64,
+	pub max_lat: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindLocationsParams {
+	pub lat: Option<f64>,
+	pub lon: Option<f64>,
+	// Radius in meters around lat/lon
+	pub radius: f64,
+	pub bbox: Option<BoundingBox>,
+	pub name: Option<String>,
+}
+
 impl Location {
     pub async fn insert(
         pool: &sqlx::PgPool,
@@ -16,14 +46,40 @@ impl Location {
             r#"
 				INSERT INTO public.locations (
 					location_id,
+					descriptive_id,
+					full_name,
+					ident_8,
+					equipment_number,
+					kilometer_point,
+					lane,
+					lambert_x,
+					lambert_y,
 					latitude,
 					longitude
 				)
-				VALUES ($1, $2, $3)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
 				ON CONFLICT (location_id)
-				DO NOTHING
+				DO UPDATE SET
+					descriptive_id = EXCLUDED.descriptive_id,
+					full_name = EXCLUDED.full_name,
+					ident_8 = EXCLUDED.ident_8,
+					equipment_number = EXCLUDED.equipment_number,
+					kilometer_point = EXCLUDED.kilometer_point,
+					lane = EXCLUDED.lane,
+					lambert_x = EXCLUDED.lambert_x,
+					lambert_y = EXCLUDED.lambert_y,
+					latitude = EXCLUDED.latitude,
+					longitude = EXCLUDED.longitude
             "#,
             location.location_id,
+            location.descriptive_id,
+            location.full_name,
+            location.ident_8,
+            location.equipment_number,
+            location.kilometer_point,
+            location.lane,
+            location.lambert_x,
+            location.lambert_y,
             location.latitude,
             location.longitude
         )
@@ -32,7 +88,7 @@ impl Location {
 
         Ok(())
     }
-	
+
 	pub async fn batch_insert(
 		pool: &sqlx::PgPool,
 		measurements: Vec<Location>,
@@ -42,12 +98,20 @@ impl Location {
 			.chunks(1000)
 			.map(|chunk| chunk.to_vec())
 			.collect();
-    
+
     	// Process each batch
 		for batch in batches {
 			let mut query_builder = String::from(
 				"INSERT INTO public.locations (
 					location_id,
+					descriptive_id,
+					full_name,
+					ident_8,
+					equipment_number,
+					kilometer_point,
+					lane,
+					lambert_x,
+					lambert_y,
 					latitude,
 					longitude
 				) VALUES "
@@ -58,18 +122,36 @@ impl Location {
 				.iter()
 				.enumerate()
 				.map(|(i, _)| {
-					let offset = i * 3;
+					let offset = i * 11;
 					format!(
-						"(${},${},${})",
+						"(${},${},${},${},${},${},${},${},${},${},${})",
 						offset + 1,
 						offset + 2,
 						offset + 3,
+						offset + 4,
+						offset + 5,
+						offset + 6,
+						offset + 7,
+						offset + 8,
+						offset + 9,
+						offset + 10,
+						offset + 11,
 					)
 				})
 				.collect();
 
 			query_builder.push_str(&values.join(","));
-			query_builder.push_str(" ON CONFLICT (location_id) DO NOTHING");
+			query_builder.push_str(" ON CONFLICT (location_id) DO UPDATE SET
+				descriptive_id = EXCLUDED.descriptive_id,
+				full_name = EXCLUDED.full_name,
+				ident_8 = EXCLUDED.ident_8,
+				equipment_number = EXCLUDED.equipment_number,
+				kilometer_point = EXCLUDED.kilometer_point,
+				lane = EXCLUDED.lane,
+				lambert_x = EXCLUDED.lambert_x,
+				lambert_y = EXCLUDED.lambert_y,
+				latitude = EXCLUDED.latitude,
+				longitude = EXCLUDED.longitude");
 
 			// Build the query
 			let mut query = sqlx::query(&query_builder);
@@ -78,6 +160,14 @@ impl Location {
 			for measurement in batch {
 				query = query
 					.bind(measurement.location_id)
+					.bind(measurement.descriptive_id)
+					.bind(measurement.full_name)
+					.bind(measurement.ident_8)
+					.bind(measurement.equipment_number)
+					.bind(measurement.kilometer_point)
+					.bind(measurement.lane)
+					.bind(measurement.lambert_x)
+					.bind(measurement.lambert_y)
 					.bind(measurement.latitude)
 					.bind(measurement.longitude);
 			}
@@ -88,4 +178,131 @@ impl Location {
 
 		Ok(())
 	}
+
+	/// Locations of the current MIV configuration, sensors removed from it are left out
+	pub async fn get_all(
+		pool: &sqlx::PgPool,
+		params: FindLocationsParams,
+	) -> Result<Vec<LocationDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(
+			"SELECT
+				location_id,
+				descriptive_id,
+				full_name,
+				ident_8,
+				equipment_number,
+				kilometer_point,
+				lane,
+				lambert_x,
+				lambert_y,
+				latitude,
+				longitude
+			FROM public.locations
+			WHERE location_id IN (SELECT location_id FROM public.location_versions WHERE valid_to IS NULL)"
+		);
+
+		if let (Some(lat), Some(lon)) = (params.lat, params.lon) {
+			query
+				.push(" AND ST_DWithin(geog, ST_SetSRID(ST_MakePoint(")
+				.push_bind(lon)
+				.push(", ")
+				.push_bind(lat)
+				.push("), 4326)::geography, ")
+				.push_bind(params.radius)
+				.push(")");
+		}
+
+		if let Some(bbox) = params.bbox {
+			query
+				.push(" AND geog && ST_MakeEnvelope(")
+				.push_bind(bbox.min_lon)
+				.push(", ")
+				.push_bind(bbox.min_lat)
+				.push(", ")
+				.push_bind(bbox.max_lon)
+				.push(", ")
+				.push_bind(bbox.max_lat)
+				.push(", 4326)::geography");
+		}
+
+		if let Some(name) = params.name {
+			let pattern = format!("%{}%", name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+			query
+				.push(" AND (full_name ILIKE ")
+				.push_bind(pattern.clone())
+				.push(" OR descriptive_id ILIKE ")
+				.push_bind(pattern)
+				.push(")");
+		}
+
+		query.push(" ORDER BY location_id");
+
+		query
+			.build_query_as::<LocationDTO>()
+			.fetch_all(pool)
+			.await
+	}
+
+	pub async fn get_by_id(
+		pool: &sqlx::PgPool,
+		location_id: i32,
+	) -> Result<Option<LocationDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			LocationDTO,
+			r#"
+			SELECT
+				location_id,
+				descriptive_id,
+				full_name,
+				ident_8,
+				equipment_number,
+				kilometer_point,
+				lane,
+				lambert_x,
+				lambert_y,
+				latitude,
+				longitude
+			FROM public.locations
+			WHERE location_id = $1
+				AND location_id IN (SELECT location_id FROM public.location_versions WHERE valid_to IS NULL)
+			"#,
+			location_id
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	/// Returns the `count` current locations closest to the given point, using a KNN index scan
+	pub async fn get_nearest(
+		pool: &sqlx::PgPool,
+		lat: f64,
+		lon: f64,
+		count: i64,
+	) -> Result<Vec<NearestLocationDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			NearestLocationDTO,
+			r#"
+			SELECT
+				location_id,
+				descriptive_id,
+				full_name,
+				ident_8,
+				equipment_number,
+				kilometer_point,
+				lane,
+				lambert_x,
+				lambert_y,
+				latitude,
+				longitude,
+				ST_Distance(geog, ST_SetSRID(ST_MakePoint($2, $1), 4326)::geography) as "distance!"
+			FROM public.locations
+			WHERE location_id IN (SELECT location_id FROM public.location_versions WHERE valid_to IS NULL)
+			ORDER BY geog <-> ST_SetSRID(ST_MakePoint($2, $1), 4326)::geography
+			LIMIT $3
+			"#,
+			lat, lon, count
+		)
+		.fetch_all(pool)
+		.await
+	}
 }
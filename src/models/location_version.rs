@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::dto::location_version::{LocationConfigurationVersionDTO, LocationMoveDTO, LocationVersionDTO};
+
+use super::location::Location;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LocationVersion {
+	pub location_id: i32,
+	pub descriptive_id: String,
+	pub full_name: String,
+	pub ident_8: String,
+	pub equipment_number: i32,
+	pub kilometer_point: Option<f64>,
+	pub lane: String,
+	pub lambert_x: f64,
+	pub lambert_y: f64,
+	pub latitude: f64,
+	pub longitude: f64,
+
+	pub valid_from: DateTime<Utc>,
+	pub valid_to: Option<DateTime<Utc>>,
+}
+
+impl LocationVersion {
+	/// Applies a MIV configuration to the location history. Locations that were
+	/// added or changed get a new version starting at `config_time`, locations that
+	/// changed or disappeared get their current version closed at `config_time`.
+	///
+	/// Returns `false` when the configuration (or a newer one) was already imported.
+	pub async fn sync(
+		pool: &sqlx::PgPool,
+		config_time: DateTime<Utc>,
+		locations: &[Location],
+	) -> Result<bool, sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		let already_imported = sqlx::query_scalar!(
+			r#"
+			SELECT EXISTS (
+				SELECT 1
+				FROM public.location_configuration_versions
+				WHERE config_time >= $1
+			) as "exists!"
+			"#,
+			config_time
+		)
+		.fetch_one(&mut *tx)
+		.await?;
+
+		if already_imported {
+			return Ok(false);
+		}
+
+		sqlx::query!(
+			r#"
+			INSERT INTO public.location_configuration_versions (
+				config_time,
+				location_count
+			)
+			VALUES ($1, $2)
+			"#,
+			config_time,
+			locations.len() as i32
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		let current: HashMap<i32, Location> = sqlx::query_as!(
+			Location,
+			r#"
+			SELECT
+				location_id,
+				descriptive_id,
+				full_name,
+				ident_8,
+				equipment_number,
+				kilometer_point,
+				lane,
+				lambert_x,
+				lambert_y,
+				latitude,
+				longitude
+			FROM public.location_versions
+			WHERE valid_to IS NULL
+			"#
+		)
+		.fetch_all(&mut *tx)
+		.await?
+		.into_iter()
+		.map(|location| (location.location_id, location))
+		.collect();
+
+		let mut to_close: Vec<i32> = vec![];
+		let mut to_insert: Vec<&Location> = vec![];
+
+		for location in locations {
+			match current.get(&location.location_id) {
+				Some(existing) if existing == location => {},
+				Some(_) => {
+					to_close.push(location.location_id);
+					to_insert.push(location);
+				},
+				None => to_insert.push(location),
+			}
+		}
+
+		let configured_ids: HashSet<i32> = locations.iter().map(|location| location.location_id).collect();
+		to_close.extend(
+			current
+				.keys()
+				.filter(|location_id| !configured_ids.contains(location_id))
+		);
+
+		sqlx::query!(
+			r#"
+			UPDATE public.location_versions
+			SET valid_to = $1
+			WHERE valid_to IS NULL AND location_id = ANY($2)
+			"#,
+			config_time,
+			&to_close
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		// Process each batch
+		for batch in to_insert.chunks(1000) {
+			let mut query_builder = String::from(
+				"INSERT INTO public.location_versions (
+					location_id,
+					descriptive_id,
+					full_name,
+					ident_8,
+					equipment_number,
+					kilometer_point,
+					lane,
+					lambert_x,
+					lambert_y,
+					latitude,
+					longitude,
+					valid_from
+				) VALUES "
+			);
+
+			// Build the values part of the query and collect params
+			let values: Vec<String> = batch
+				.iter()
+				.enumerate()
+				.map(|(i, _)| {
+					let offset = i * 12;
+					format!(
+						"(${},${},${},${},${},${},${},${},${},${},${},${})",
+						offset + 1,
+						offset + 2,
+						offset + 3,
+						offset + 4,
+						offset + 5,
+						offset + 6,
+						offset + 7,
+						offset + 8,
+						offset + 9,
+						offset + 10,
+						offset + 11,
+						offset + 12,
+					)
+				})
+				.collect();
+
+			query_builder.push_str(&values.join(","));
+
+			// Build the query
+			let mut query = sqlx::query(&query_builder);
+
+			// Add parameters for each location
+			for location in batch {
+				query = query
+					.bind(location.location_id)
+					.bind(&location.descriptive_id)
+					.bind(&location.full_name)
+					.bind(&location.ident_8)
+					.bind(location.equipment_number)
+					.bind(location.kilometer_point)
+					.bind(&location.lane)
+					.bind(location.lambert_x)
+					.bind(location.lambert_y)
+					.bind(location.latitude)
+					.bind(location.longitude)
+					.bind(config_time);
+			}
+
+			// Execute the batch insert
+			query.execute(&mut *tx).await?;
+		}
+
+		tx.commit().await?;
+
+		Ok(true)
+	}
+
+	pub async fn get_versions(
+		pool: &sqlx::PgPool,
+	) -> Result<Vec<LocationConfigurationVersionDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			LocationConfigurationVersionDTO,
+			r#"
+			SELECT
+				config_time,
+				imported_at,
+				location_count
+			FROM public.location_configuration_versions
+			ORDER BY config_time DESC
+			"#
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	pub async fn get_at(
+		pool: &sqlx::PgPool,
+		at: DateTime<Utc>,
+	) -> Result<Vec<LocationVersionDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			LocationVersionDTO,
+			r#"
+			SELECT
+				location_id,
+				descriptive_id,
+				full_name,
+				ident_8,
+				equipment_number,
+				kilometer_point,
+				lane,
+				lambert_x,
+				lambert_y,
+				latitude,
+				longitude,
+				valid_from,
+				valid_to
+			FROM public.location_versions
+			WHERE valid_from <= $1 AND (valid_to IS NULL OR valid_to > $1)
+			ORDER BY location_id
+			"#,
+			at
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Returns the locations that are configured at `to` but were not configured at `from`.
+	/// Swap the arguments to get the removed locations.
+	pub async fn get_added(
+		pool: &sqlx::PgPool,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<LocationVersionDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			LocationVersionDTO,
+			r#"
+			SELECT
+				b.location_id,
+				b.descriptive_id,
+				b.full_name,
+				b.ident_8,
+				b.equipment_number,
+				b.kilometer_point,
+				b.lane,
+				b.lambert_x,
+				b.lambert_y,
+				b.latitude,
+				b.longitude,
+				b.valid_from,
+				b.valid_to
+			FROM public.location_versions b
+			WHERE b.valid_from <= $2 AND (b.valid_to IS NULL OR b.valid_to > $2)
+				AND NOT EXISTS (
+					SELECT 1
+					FROM public.location_versions a
+					WHERE a.location_id = b.location_id
+						AND a.valid_from <= $1 AND (a.valid_to IS NULL OR a.valid_to > $1)
+				)
+			ORDER BY b.location_id
+			"#,
+			from, to
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	pub async fn get_moved(
+		pool: &sqlx::PgPool,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<LocationMoveDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			LocationMoveDTO,
+			r#"
+			SELECT
+				b.location_id,
+				b.descriptive_id,
+				b.full_name,
+				a.latitude as from_latitude,
+				a.longitude as from_longitude,
+				b.latitude as to_latitude,
+				b.longitude as to_longitude,
+				ST_Distance(
+					ST_SetSRID(ST_MakePoint(a.longitude, a.latitude), 4326)::geography,
+					ST_SetSRID(ST_MakePoint(b.longitude, b.latitude), 4326)::geography
+				) as distance
+			FROM public.location_versions a
+			INNER JOIN public.location_versions b ON a.location_id = b.location_id
+			WHERE a.valid_from <= $1 AND (a.valid_to IS NULL OR a.valid_to > $1)
+				AND b.valid_from <= $2 AND (b.valid_to IS NULL OR b.valid_to > $2)
+				AND (a.latitude <> b.latitude OR a.longitude <> b.longitude)
+			ORDER BY b.location_id
+			"#,
+			from, to
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
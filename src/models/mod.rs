@@ -1,2 +1,17 @@
 pub mod traffic_measurement;
 pub mod location;
+pub mod traffic_measurement_class;
+pub mod location_version;
+pub mod current_traffic_measurement;
+pub mod traffic_aggregate;
+pub mod storage_policy;
+pub mod traffic_profile;
+pub mod congestion;
+pub mod traffic_anomaly;
+pub mod site;
+pub mod travel_time;
+pub mod queue;
+pub mod road_kpi;
+pub mod emission;
+pub mod sensor_health;
+pub mod plausibility;
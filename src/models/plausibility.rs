@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Effective length in meters a vehicle keeps the loop occupied: vehicle and loop length
+const EFFECTIVE_VEHICLE_LENGTH: f64 = 7.0;
+// Occupancy percentage points the observed occupancy may always be off by
+const OCCUPANCY_MARGIN: f64 = 5.0;
+
+/// Reason a measurement failed a plausibility check
+#[derive(sqlx::Type, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
+#[sqlx(type_name = "plausibility_flag", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PlausibilityFlag {
+	StuckValue,
+	DuplicateLane,
+	SpeedAboveLimit,
+	OccupancyMismatch,
+}
+
+/// Thresholds of the plausibility checks, `None` disables the check
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlausibilityRules {
+	// Minutes a location has to repeat the exact same reading to be stuck
+	pub stuck_minutes: Option<i32>,
+	// Vehicles from which identical lanes of a station no longer count as coincidence
+	pub duplicate_min_vehicles: Option<i32>,
+	// km/h
+	pub max_speed: Option<i32>,
+	// Factor the occupancy may differ from the one expected from intensity and speed
+	pub occupancy_tolerance: Option<f64>,
+}
+
+pub struct Plausibility;
+
+impl Plausibility {
+	/// Flags the measurements observed since `since` that fail one of the rules.
+	/// Measurements are never dropped, every checked row gets its (possibly empty)
+	/// list of reason codes. Returns the number of flagged rows.
+	pub async fn check_since(
+		pool: &sqlx::PgPool,
+		since: DateTime<Utc>,
+		rules: &PlausibilityRules,
+	) -> Result<u64, sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		// Expected occupancy in percent: vehicles per second times the seconds each keeps the loop occupied
+		sqlx::query!(
+			r#"
+			WITH checks AS (
+				SELECT
+					t.location_id,
+					t.observation_time,
+					ARRAY_REMOVE(ARRAY[
+						CASE WHEN $2::INTEGER IS NOT NULL AND GREATEST(t.max_speed, t.average_speed) > $2
+							THEN 'SPEED_ABOVE_LIMIT'::plausibility_flag END,
+						CASE WHEN $3::DOUBLE PRECISION IS NOT NULL
+							AND t.occupancy_rate IS NOT NULL
+							AND (t.occupancy_rate > e.expected_occupancy * $3 + $5::DOUBLE PRECISION
+								OR t.occupancy_rate < e.expected_occupancy / $3 - $5)
+							THEN 'OCCUPANCY_MISMATCH'::plausibility_flag END,
+						CASE WHEN $6::INTEGER IS NOT NULL AND t.total_vehicles_passed >= $6 AND EXISTS (
+								SELECT 1
+								FROM public.locations l
+								INNER JOIN public.locations n
+									ON n.equipment_number = l.equipment_number AND n.location_id <> l.location_id
+								INNER JOIN public.traffic_measurements o
+									ON o.location_id = n.location_id AND o.observation_time = t.observation_time
+								WHERE l.location_id = t.location_id
+									AND o.total_vehicles_passed = t.total_vehicles_passed
+									AND o.average_speed IS NOT DISTINCT FROM t.average_speed
+									AND o.max_speed IS NOT DISTINCT FROM t.max_speed
+									AND o.occupancy_rate IS NOT DISTINCT FROM t.occupancy_rate
+							)
+							THEN 'DUPLICATE_LANE'::plausibility_flag END
+					], NULL) AS plausibility_flags
+				FROM public.traffic_measurements t
+				CROSS JOIN LATERAL (
+					SELECT 100.0 * t.total_vehicles_passed / 60.0
+						* $4::DOUBLE PRECISION / (NULLIF(COALESCE(t.space_mean_speed, t.average_speed), 0) / 3.6) AS expected_occupancy
+				) e
+				WHERE t.observation_time >= $1
+			)
+			UPDATE public.traffic_measurements t
+			SET plausibility_flags = c.plausibility_flags
+			FROM checks c
+			WHERE t.location_id = c.location_id
+				AND t.observation_time = c.observation_time
+				AND t.observation_time >= $1
+			"#,
+			since,
+			rules.max_speed,
+			rules.occupancy_tolerance,
+			EFFECTIVE_VEHICLE_LENGTH,
+			OCCUPANCY_MARGIN,
+			rules.duplicate_min_vehicles
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		// A stuck sensor is only recognised after the fact, so the whole stretch gets flagged.
+		// Readings without any traffic are left alone, an empty road at night repeats itself too.
+		if let Some(stuck_minutes) = rules.stuck_minutes {
+			sqlx::query!(
+				r#"
+				WITH stuck AS (
+					SELECT t.location_id, t.observation_time
+					FROM public.traffic_measurements t
+					CROSS JOIN LATERAL (
+						SELECT
+							COUNT(*) AS reading_count,
+							COUNT(*) FILTER (
+								WHERE p.total_vehicles_passed IS NOT DISTINCT FROM t.total_vehicles_passed
+									AND p.average_speed IS NOT DISTINCT FROM t.average_speed
+									AND p.occupancy_rate IS NOT DISTINCT FROM t.occupancy_rate
+							) AS identical_count
+						FROM public.traffic_measurements p
+						WHERE p.location_id = t.location_id
+							AND p.observation_time > t.observation_time - make_interval(mins => $2)
+							AND p.observation_time <= t.observation_time
+					) w
+					WHERE t.observation_time >= $1
+						AND (t.total_vehicles_passed > 0 OR t.occupancy_rate > 0)
+						-- Tolerate gaps in the feed, but not a window with hardly any readings
+						AND w.reading_count >= $2 / 2
+						AND w.identical_count = w.reading_count
+				)
+				UPDATE public.traffic_measurements t
+				SET plausibility_flags = ARRAY_APPEND(COALESCE(t.plausibility_flags, '{}'), 'STUCK_VALUE')
+				FROM stuck s
+				WHERE t.location_id = s.location_id
+					AND t.observation_time > s.observation_time - make_interval(mins => $2)
+					AND t.observation_time <= s.observation_time
+					AND NOT ('STUCK_VALUE' = ANY(COALESCE(t.plausibility_flags, '{}')))
+				"#,
+				since,
+				stuck_minutes
+			)
+			.execute(&mut *tx)
+			.await?;
+		}
+
+		let flagged = sqlx::query!(
+			r#"
+			SELECT COUNT(*) as "count!"
+			FROM public.traffic_measurements
+			WHERE observation_time >= $1 AND plausibility_flags <> '{}'
+			"#,
+			since
+		)
+		.fetch_one(&mut *tx)
+		.await?;
+
+		tx.commit().await?;
+
+		Ok(flagged.count as u64)
+	}
+}
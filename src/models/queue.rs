@@ -0,0 +1,560 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::dto::queue::{QueueEventDTO, QueueSnapshotDTO};
+
+use super::{congestion::CongestionLevel, current_traffic_measurement::MAX_READING_AGE_MINUTES, site::RoadDirection};
+
+// Queues that are not seen for this long have dissolved
+const QUEUE_END_GRACE_MINUTES: i64 = 5;
+
+/// Current reading of a main carriageway site, combined across its lanes
+struct SiteState {
+	site_id: String,
+	road: String,
+	direction: RoadDirection,
+	kilometer_point: f64,
+	observation_time: Option<DateTime<Utc>>,
+	space_mean_speed: Option<f64>,
+	congestion_level: Option<CongestionLevel>,
+	reach_before_km: f64,
+	reach_after_km: f64,
+}
+
+impl SiteState {
+	// A site without a reading is never queued, so a queue never spans road nobody measured
+	fn is_queued(&self) -> bool {
+		self.observation_time.is_some()
+			&& matches!(self.congestion_level, Some(CongestionLevel::Slow | CongestionLevel::Stationary))
+	}
+
+	fn same_corridor(&self, other: &SiteState) -> bool {
+		self.road == other.road && self.direction == other.direction
+	}
+
+	/// Road the site stands for against the direction of travel
+	fn reach_upstream(&self) -> f64 {
+		match self.direction {
+			RoadDirection::Positive => self.reach_before_km,
+			RoadDirection::Negative => self.reach_after_km,
+		}
+	}
+
+	/// Road the site stands for in the direction of travel
+	fn reach_downstream(&self) -> f64 {
+		match self.direction {
+			RoadDirection::Positive => self.reach_after_km,
+			RoadDirection::Negative => self.reach_before_km,
+		}
+	}
+}
+
+/// A queue as seen in a single ingestion run
+#[derive(Debug, Clone)]
+struct DetectedQueue {
+	road: String,
+	direction: RoadDirection,
+	tail_site_id: String,
+	head_site_id: String,
+	tail_kilometer_point: f64,
+	head_kilometer_point: f64,
+	length_km: f64,
+	site_count: i32,
+	min_speed: Option<f64>,
+	average_speed: Option<f64>,
+}
+
+impl DetectedQueue {
+	fn overlaps(&self, tail_kilometer_point: f64, head_kilometer_point: f64) -> bool {
+		let (start, end) = (self.tail_kilometer_point.min(self.head_kilometer_point), self.tail_kilometer_point.max(self.head_kilometer_point));
+
+		tail_kilometer_point.min(head_kilometer_point) <= end && tail_kilometer_point.max(head_kilometer_point) >= start
+	}
+}
+
+/// Finds the runs of consecutive queued sites. `sites` has to be in driving order per
+/// road and direction, a site without a reading ends the run.
+fn detect_queues(sites: &[SiteState]) -> Vec<DetectedQueue> {
+	let mut queues = vec![];
+	let mut i = 0;
+
+	while i < sites.len() {
+		if !sites[i].is_queued() {
+			i += 1;
+			continue;
+		}
+
+		let start = i;
+		while i + 1 < sites.len() && sites[i + 1].same_corridor(&sites[i]) && sites[i + 1].is_queued() {
+			i += 1;
+		}
+
+		let run = &sites[start..=i];
+		let (tail, head) = (&run[0], &run[run.len() - 1]);
+		let sign = match tail.direction {
+			RoadDirection::Positive => 1.0,
+			RoadDirection::Negative => -1.0,
+		};
+
+		let tail_kilometer_point = tail.kilometer_point - sign * tail.reach_upstream();
+		let head_kilometer_point = head.kilometer_point + sign * head.reach_downstream();
+
+		let speeds: Vec<f64> = run.iter().filter_map(|site| site.space_mean_speed).collect();
+
+		queues.push(DetectedQueue {
+			road: tail.road.clone(),
+			direction: tail.direction,
+			tail_site_id: tail.site_id.clone(),
+			head_site_id: head.site_id.clone(),
+			tail_kilometer_point,
+			head_kilometer_point,
+			length_km: (head_kilometer_point - tail_kilometer_point).abs(),
+			site_count: run.len() as i32,
+			min_speed: speeds.iter().copied().reduce(f64::min),
+			average_speed: (!speeds.is_empty()).then(|| speeds.iter().sum::<f64>() / speeds.len() as f64),
+		});
+
+		i += 1;
+	}
+
+	queues
+}
+
+pub struct QueueEvent;
+
+impl QueueEvent {
+	/// Detects the queues in the current readings that are at most `MAX_READING_AGE_MINUTES`
+	/// old, extends the open queue events they overlap or starts new ones, and ends the
+	/// events that have not been seen for a while. Returns the number of queues.
+	pub async fn detect(
+		pool: &sqlx::PgPool,
+	) -> Result<usize, sqlx::Error> {
+		let since = Utc::now() - Duration::minutes(MAX_READING_AGE_MINUTES);
+
+		let sites = sqlx::query_as!(
+			SiteState,
+			r#"
+			SELECT
+				s.site_id as "site_id!",
+				s.road as "road!",
+				s.direction as "direction!: RoadDirection",
+				s.kilometer_point as "kilometer_point!",
+				c.observation_time,
+				c.space_mean_speed,
+				classify_congestion(
+					c.time_mean_speed,
+					c.occupancy_rate,
+					c.total_vehicles_passed,
+					c.free_flow_speed
+				) as "congestion_level: CongestionLevel",
+				r.reach_before_km as "reach_before_km!",
+				r.reach_after_km as "reach_after_km!"
+			FROM public.measuring_sites s
+			INNER JOIN public.measuring_site_reaches r ON r.site_id = s.site_id
+			CROSS JOIN LATERAL (
+				SELECT
+					MAX(t.observation_time) as observation_time,
+					-- Free-flow speeds are learned from the time-mean speed, classify on the same
+					SUM(t.total_vehicles_passed * t.time_mean_speed)
+						/ NULLIF(SUM(t.total_vehicles_passed) FILTER (WHERE t.time_mean_speed IS NOT NULL), 0) as time_mean_speed,
+					SUM(t.total_vehicles_passed) FILTER (WHERE t.space_mean_speed > 0)
+						/ NULLIF(SUM(t.total_vehicles_passed / t.space_mean_speed) FILTER (WHERE t.space_mean_speed > 0), 0) as space_mean_speed,
+					AVG(t.occupancy_rate)::INTEGER as occupancy_rate,
+					SUM(t.total_vehicles_passed)::INTEGER as total_vehicles_passed,
+					AVG(f.free_flow_speed) as free_flow_speed
+				FROM public.current_traffic_measurements t
+				LEFT JOIN public.free_flow_speeds f ON f.location_id = t.location_id
+				WHERE t.location_id = ANY(s.location_ids)
+					AND t.observation_time >= $1
+					AND is_valid_measurement(t.valid, t.available, t.faulty, t.plausibility_flags)
+			) c
+			ORDER BY
+				s.road,
+				s.direction,
+				CASE WHEN s.direction = 'POSITIVE' THEN s.kilometer_point ELSE -s.kilometer_point END
+			"#,
+			since
+		)
+		.fetch_all(pool)
+		.await?;
+
+		let Some(observation_time) = sites.iter().filter_map(|site| site.observation_time).max() else {
+			return Ok(0);
+		};
+
+		let queues = detect_queues(&sites);
+
+		let mut tx = pool.begin().await?;
+
+		let open_events = sqlx::query!(
+			r#"
+			SELECT
+				queue_id,
+				road,
+				direction as "direction: RoadDirection",
+				tail_kilometer_point,
+				head_kilometer_point
+			FROM public.queue_events
+			WHERE ended_at IS NULL
+			ORDER BY started_at
+			"#
+		)
+		.fetch_all(&mut *tx)
+		.await?;
+
+		let mut seen: HashSet<i32> = HashSet::new();
+
+		for queue in &queues {
+			let existing = open_events.iter().find(|event| {
+				!seen.contains(&event.queue_id)
+					&& event.road == queue.road
+					&& event.direction == queue.direction
+					&& queue.overlaps(event.tail_kilometer_point, event.head_kilometer_point)
+			});
+
+			let queue_id = match existing {
+				Some(event) => {
+					sqlx::query!(
+						r#"
+						UPDATE public.queue_events
+						SET
+							last_seen_at = $2,
+							tail_site_id = $3,
+							head_site_id = $4,
+							tail_kilometer_point = $5,
+							head_kilometer_point = $6,
+							length_km = $7,
+							max_length_km = GREATEST(max_length_km, $7)
+						WHERE queue_id = $1
+						"#,
+						event.queue_id,
+						observation_time,
+						queue.tail_site_id,
+						queue.head_site_id,
+						queue.tail_kilometer_point,
+						queue.head_kilometer_point,
+						queue.length_km
+					)
+					.execute(&mut *tx)
+					.await?;
+
+					event.queue_id
+				},
+				None => sqlx::query_scalar!(
+					r#"
+					INSERT INTO public.queue_events (
+						road,
+						direction,
+						started_at,
+						last_seen_at,
+						tail_site_id,
+						head_site_id,
+						tail_kilometer_point,
+						head_kilometer_point,
+						length_km,
+						max_length_km
+					)
+					VALUES ($1, $2, $3, $3, $4, $5, $6, $7, $8, $8)
+					RETURNING queue_id
+					"#,
+					queue.road,
+					queue.direction as RoadDirection,
+					observation_time,
+					queue.tail_site_id,
+					queue.head_site_id,
+					queue.tail_kilometer_point,
+					queue.head_kilometer_point,
+					queue.length_km
+				)
+				.fetch_one(&mut *tx)
+				.await?,
+			};
+
+			seen.insert(queue_id);
+
+			sqlx::query!(
+				r#"
+				INSERT INTO public.queue_event_snapshots (
+					queue_id,
+					observation_time,
+					tail_site_id,
+					head_site_id,
+					tail_kilometer_point,
+					head_kilometer_point,
+					length_km,
+					site_count,
+					min_speed,
+					average_speed
+				)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+				ON CONFLICT (queue_id, observation_time) DO NOTHING
+				"#,
+				queue_id,
+				observation_time,
+				queue.tail_site_id,
+				queue.head_site_id,
+				queue.tail_kilometer_point,
+				queue.head_kilometer_point,
+				queue.length_km,
+				queue.site_count,
+				queue.min_speed,
+				queue.average_speed
+			)
+			.execute(&mut *tx)
+			.await?;
+		}
+
+		sqlx::query!(
+			r#"
+			UPDATE public.queue_events
+			SET ended_at = last_seen_at
+			WHERE ended_at IS NULL AND last_seen_at < $1
+			"#,
+			observation_time - Duration::minutes(QUEUE_END_GRACE_MINUTES)
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		tx.commit().await?;
+
+		Ok(queues.len())
+	}
+
+	pub async fn get_current(
+		pool: &sqlx::PgPool,
+		road: Option<String>,
+		direction: Option<RoadDirection>,
+	) -> Result<Vec<QueueEventDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			QueueEventDTO,
+			r#"
+			SELECT
+				q.queue_id,
+				q.road,
+				q.direction as "direction: RoadDirection",
+				q.started_at,
+				q.last_seen_at,
+				q.ended_at,
+				q.tail_site_id,
+				t.full_name as tail_name,
+				q.tail_kilometer_point,
+				q.head_site_id,
+				h.full_name as head_name,
+				q.head_kilometer_point,
+				q.length_km,
+				q.max_length_km
+			FROM public.queue_events q
+			LEFT JOIN public.measuring_sites t ON t.site_id = q.tail_site_id
+			LEFT JOIN public.measuring_sites h ON h.site_id = q.head_site_id
+			WHERE q.ended_at IS NULL
+				AND ($1::TEXT IS NULL OR q.road = $1)
+				AND ($2::road_direction IS NULL OR q.direction = $2)
+			ORDER BY q.length_km DESC
+			"#,
+			road, direction as Option<RoadDirection>
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Queues that were present at some point between `from` and `to`
+	pub async fn get_history(
+		pool: &sqlx::PgPool,
+		road: Option<String>,
+		direction: Option<RoadDirection>,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<QueueEventDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			QueueEventDTO,
+			r#"
+			SELECT
+				q.queue_id,
+				q.road,
+				q.direction as "direction: RoadDirection",
+				q.started_at,
+				q.last_seen_at,
+				q.ended_at,
+				q.tail_site_id,
+				t.full_name as tail_name,
+				q.tail_kilometer_point,
+				q.head_site_id,
+				h.full_name as head_name,
+				q.head_kilometer_point,
+				q.length_km,
+				q.max_length_km
+			FROM public.queue_events q
+			LEFT JOIN public.measuring_sites t ON t.site_id = q.tail_site_id
+			LEFT JOIN public.measuring_sites h ON h.site_id = q.head_site_id
+			WHERE q.started_at < $4
+				AND COALESCE(q.ended_at, 'infinity'::timestamptz) >= $3
+				AND ($1::TEXT IS NULL OR q.road = $1)
+				AND ($2::road_direction IS NULL OR q.direction = $2)
+			ORDER BY q.started_at DESC
+			"#,
+			road, direction as Option<RoadDirection>, from, to
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	pub async fn get_by_id(
+		pool: &sqlx::PgPool,
+		queue_id: i32,
+	) -> Result<Option<QueueEventDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			QueueEventDTO,
+			r#"
+			SELECT
+				q.queue_id,
+				q.road,
+				q.direction as "direction: RoadDirection",
+				q.started_at,
+				q.last_seen_at,
+				q.ended_at,
+				q.tail_site_id,
+				t.full_name as tail_name,
+				q.tail_kilometer_point,
+				q.head_site_id,
+				h.full_name as head_name,
+				q.head_kilometer_point,
+				q.length_km,
+				q.max_length_km
+			FROM public.queue_events q
+			LEFT JOIN public.measuring_sites t ON t.site_id = q.tail_site_id
+			LEFT JOIN public.measuring_sites h ON h.site_id = q.head_site_id
+			WHERE q.queue_id = $1
+			"#,
+			queue_id
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	pub async fn get_snapshots(
+		pool: &sqlx::PgPool,
+		queue_id: i32,
+	) -> Result<Vec<QueueSnapshotDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			QueueSnapshotDTO,
+			r#"
+			SELECT
+				observation_time,
+				tail_site_id,
+				head_site_id,
+				tail_kilometer_point,
+				head_kilometer_point,
+				length_km,
+				site_count,
+				min_speed,
+				average_speed
+			FROM public.queue_event_snapshots
+			WHERE queue_id = $1
+			ORDER BY observation_time
+			"#,
+			queue_id
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn site(site_id: &str, road: &str, direction: RoadDirection, kilometer_point: f64, congestion_level: CongestionLevel, space_mean_speed: f64) -> SiteState {
+		SiteState {
+			site_id: site_id.to_owned(),
+			road: road.to_owned(),
+			direction,
+			kilometer_point,
+			observation_time: Some(Utc::now()),
+			space_mean_speed: Some(space_mean_speed),
+			congestion_level: Some(congestion_level),
+			reach_before_km: 0.5,
+			reach_after_km: 0.5,
+		}
+	}
+
+	fn silent(mut site: SiteState) -> SiteState {
+		site.observation_time = None;
+		site.space_mean_speed = None;
+		site
+	}
+
+	#[test]
+	fn finds_a_run_of_queued_sites_with_its_reach() {
+		let sites = [
+			site("a", "E40", RoadDirection::Positive, 9.0, CongestionLevel::FreeFlow, 110.0),
+			site("b", "E40", RoadDirection::Positive, 10.0, CongestionLevel::Slow, 40.0),
+			site("c", "E40", RoadDirection::Positive, 11.0, CongestionLevel::Stationary, 10.0),
+			site("d", "E40", RoadDirection::Positive, 12.0, CongestionLevel::Dense, 70.0),
+		];
+
+		let queues = detect_queues(&sites);
+
+		assert_eq!(queues.len(), 1);
+		let queue = &queues[0];
+		assert_eq!((queue.tail_site_id.as_str(), queue.head_site_id.as_str()), ("b", "c"));
+		assert_eq!((queue.tail_kilometer_point, queue.head_kilometer_point), (9.5, 11.5));
+		assert_eq!(queue.length_km, 2.0);
+		assert_eq!(queue.site_count, 2);
+		assert_eq!(queue.min_speed, Some(10.0));
+		assert_eq!(queue.average_speed, Some(25.0));
+	}
+
+	#[test]
+	fn extends_against_decreasing_kilometre_points() {
+		let mut tail = site("b", "E40", RoadDirection::Negative, 11.0, CongestionLevel::Slow, 40.0);
+		tail.reach_after_km = 1.0;
+		let mut head = site("c", "E40", RoadDirection::Negative, 10.0, CongestionLevel::Slow, 30.0);
+		head.reach_before_km = 0.25;
+
+		let queues = detect_queues(&[tail, head]);
+
+		assert_eq!(queues.len(), 1);
+		assert_eq!((queues[0].tail_kilometer_point, queues[0].head_kilometer_point), (12.0, 9.75));
+		assert_eq!(queues[0].length_km, 2.25);
+	}
+
+	#[test]
+	fn a_site_without_a_reading_ends_the_run() {
+		let sites = [
+			site("a", "E40", RoadDirection::Positive, 10.0, CongestionLevel::Slow, 40.0),
+			silent(site("b", "E40", RoadDirection::Positive, 11.0, CongestionLevel::Slow, 40.0)),
+			site("c", "E40", RoadDirection::Positive, 12.0, CongestionLevel::Slow, 40.0),
+		];
+
+		let queues = detect_queues(&sites);
+
+		assert_eq!(queues.len(), 2);
+		assert_eq!(queues[0].head_site_id, "a");
+		assert_eq!(queues[1].tail_site_id, "c");
+	}
+
+	#[test]
+	fn runs_do_not_cross_corridors() {
+		let sites = [
+			site("a", "E40", RoadDirection::Positive, 20.0, CongestionLevel::Slow, 40.0),
+			site("b", "E40", RoadDirection::Negative, 20.0, CongestionLevel::Slow, 40.0),
+			site("c", "E19", RoadDirection::Negative, 1.0, CongestionLevel::Slow, 40.0),
+		];
+
+		let queues = detect_queues(&sites);
+
+		assert_eq!(queues.len(), 3);
+		assert!(queues.iter().all(|queue| queue.site_count == 1));
+	}
+
+	#[test]
+	fn no_queue_without_slow_sites() {
+		let sites = [
+			site("a", "E40", RoadDirection::Positive, 10.0, CongestionLevel::FreeFlow, 110.0),
+			site("b", "E40", RoadDirection::Positive, 11.0, CongestionLevel::Dense, 70.0),
+		];
+
+		assert!(detect_queues(&sites).is_empty());
+	}
+}
@@ -0,0 +1,185 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::dto::kpi::RoadKpiDTO;
+
+use super::site::RoadDirection;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KpiPeriod {
+	Day,
+	Month,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindRoadKpisParams {
+	pub road: Option<String>,
+	pub direction: Option<RoadDirection>,
+	pub from: NaiveDate,
+	// Inclusive
+	pub to: NaiveDate,
+	pub period: KpiPeriod,
+}
+
+pub struct RoadKpi;
+
+impl RoadKpi {
+	/// Computes the KPIs of every road for the local calendar `day` in `time_zone`.
+	/// Per site and hour the vehicle-hours are the represented length times the sum of
+	/// vehicles over speed, the lost hours what exceeds the same at free-flow speed.
+	/// The totals are not scaled up over gaps in the data, the coverage stored next to
+	/// them tells how much of the road and day had readings.
+	/// Returns the number of roads and directions stored.
+	pub async fn refresh_day(
+		pool: &sqlx::PgPool,
+		day: NaiveDate,
+		time_zone: &str,
+	) -> Result<u64, sqlx::Error> {
+		let result = sqlx::query!(
+			r#"
+			WITH site_lengths AS (
+				SELECT
+					s.site_id,
+					s.road,
+					s.direction,
+					s.location_ids,
+					s.lane_count,
+					r.reach_before_km + r.reach_after_km AS represented_km
+				FROM public.measuring_sites s
+				INNER JOIN public.measuring_site_reaches r ON r.site_id = s.site_id
+			),
+			road_lengths AS (
+				SELECT road, direction, SUM(represented_km) AS total_km
+				FROM site_lengths
+				GROUP BY road, direction
+			),
+			site_hours AS (
+				SELECT
+					s.site_id,
+					s.road,
+					s.direction,
+					s.represented_km,
+					h.bucket,
+					SUM(h.total_vehicles_passed)::DOUBLE PRECISION AS vehicles,
+					-- Vehicles without a speed reading are assumed to drive at the harmonic mean
+					SUM(h.inverse_speed_sum) * SUM(h.total_vehicles_passed) / NULLIF(SUM(h.space_mean_vehicles), 0) AS hours_per_km,
+					AVG(f.free_flow_speed) AS free_flow_speed,
+					-- Share of the lane-minutes of the hour with a valid reading
+					SUM(h.sample_count)::DOUBLE PRECISION / (60 * s.lane_count) AS hour_coverage
+				FROM site_lengths s
+				INNER JOIN public.traffic_measurements_hourly h ON h.location_id = ANY(s.location_ids)
+				LEFT JOIN public.free_flow_speeds f ON f.location_id = h.location_id
+				WHERE h.bucket >= $1::DATE::TIMESTAMP AT TIME ZONE $2
+					AND h.bucket < ($1::DATE + 1)::TIMESTAMP AT TIME ZONE $2
+				GROUP BY s.site_id, s.road, s.direction, s.represented_km, s.lane_count, h.bucket
+			),
+			site_days AS (
+				SELECT
+					site_id,
+					road,
+					direction,
+					represented_km,
+					SUM(vehicles) * represented_km AS vehicle_km,
+					SUM(hours_per_km) * represented_km AS vehicle_hours,
+					SUM(GREATEST(hours_per_km - vehicles / NULLIF(free_flow_speed, 0), 0)) * represented_km AS vehicle_hours_lost,
+					SUM(LEAST(hour_coverage, 1)) AS covered_hours
+				FROM site_hours
+				GROUP BY site_id, road, direction, represented_km
+			)
+			INSERT INTO public.road_daily_kpis (
+				day,
+				road,
+				direction,
+				site_count,
+				covered_km,
+				vehicle_km,
+				vehicle_hours,
+				vehicle_hours_lost,
+				coverage,
+				computed_at
+			)
+			SELECT
+				$1,
+				d.road,
+				d.direction,
+				COUNT(*),
+				SUM(d.represented_km),
+				COALESCE(SUM(d.vehicle_km), 0),
+				COALESCE(SUM(d.vehicle_hours), 0),
+				COALESCE(SUM(d.vehicle_hours_lost), 0),
+				-- Length weighted share of the hours of the local day, 23 or 25 on a DST change
+				SUM(d.represented_km * d.covered_hours) / NULLIF(r.total_km * EXTRACT(EPOCH FROM
+					(($1::DATE + 1)::TIMESTAMP AT TIME ZONE $2) - ($1::DATE::TIMESTAMP AT TIME ZONE $2)
+				) / 3600, 0),
+				NOW()
+			FROM site_days d
+			INNER JOIN road_lengths r ON r.road = d.road AND r.direction = d.direction
+			GROUP BY d.road, d.direction, r.total_km
+			ON CONFLICT (day, road, direction) DO UPDATE SET
+				site_count = EXCLUDED.site_count,
+				covered_km = EXCLUDED.covered_km,
+				vehicle_km = EXCLUDED.vehicle_km,
+				vehicle_hours = EXCLUDED.vehicle_hours,
+				vehicle_hours_lost = EXCLUDED.vehicle_hours_lost,
+				coverage = EXCLUDED.coverage,
+				computed_at = EXCLUDED.computed_at
+			"#,
+			day, time_zone
+		)
+		.execute(pool)
+		.await?;
+
+		Ok(result.rows_affected())
+	}
+
+	/// Current calendar day in `time_zone`, the days the KPIs are bucketed by
+	pub async fn local_today(
+		pool: &sqlx::PgPool,
+		time_zone: &str,
+	) -> Result<NaiveDate, sqlx::Error> {
+		sqlx::query_scalar!(
+			r#"SELECT (NOW() AT TIME ZONE $1)::DATE as "today!""#,
+			time_zone
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	pub async fn get_all(
+		pool: &sqlx::PgPool,
+		params: FindRoadKpisParams,
+	) -> Result<Vec<RoadKpiDTO>, sqlx::Error> {
+		let period = match params.period {
+			KpiPeriod::Day => "day",
+			KpiPeriod::Month => "month",
+		};
+
+		sqlx::query_as!(
+			RoadKpiDTO,
+			r#"
+			SELECT
+				date_trunc($5, day)::DATE as "period_start!",
+				road,
+				direction as "direction: RoadDirection",
+				COUNT(*) as "day_count!",
+				MAX(site_count) as "site_count!",
+				AVG(covered_km) as "covered_km!",
+				SUM(vehicle_km) as "vehicle_km!",
+				SUM(vehicle_hours) as "vehicle_hours!",
+				SUM(vehicle_hours_lost) as "vehicle_hours_lost!",
+				SUM(vehicle_km) / NULLIF(SUM(vehicle_hours), 0) as average_speed,
+				AVG(coverage) as coverage
+			FROM public.road_daily_kpis
+			WHERE day >= $3 AND day <= $4
+				AND ($1::TEXT IS NULL OR road = $1)
+				AND ($2::road_direction IS NULL OR direction = $2)
+			GROUP BY 1, road, direction
+			ORDER BY 1, road, direction
+			"#,
+			params.road, params.direction as Option<RoadDirection>, params.from, params.to, period
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
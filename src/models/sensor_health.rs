@@ -0,0 +1,541 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::dto::sensor_health::{EquipmentHealthDTO, EquipmentOutageDTO, LocationHealthDTO};
+
+use super::{current_traffic_measurement::MAX_READING_AGE_MINUTES, location::BoundingBox, traffic_measurement::VALID_MEASUREMENTS_FILTER};
+
+// A sensor is silent when its last valid reading is this far behind the rest of the network
+pub const SILENT_AFTER_MINUTES: i64 = MAX_READING_AGE_MINUTES;
+// Share of defective and unavailable readings within the window that marks a sensor degraded
+pub const DEGRADED_SHARE: f64 = 0.2;
+pub const HEALTH_WINDOW_HOURS: i64 = 24;
+
+// Pushed after `VALID_MEASUREMENTS_FILTER`: a reading with the sensor error speed code
+// does not prove the sensor works either
+const SENSOR_ERROR_FILTER: &str = " AND t.data_quality IS DISTINCT FROM 'SENSOR_ERROR'";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SensorStatus {
+	Ok,
+	Degraded,
+	Silent,
+}
+
+impl SensorStatus {
+	pub fn classify(
+		last_valid_observation_time: Option<DateTime<Utc>>,
+		reference_time: Option<DateTime<Utc>>,
+		reading_count: i64,
+		bad_reading_count: i64,
+	) -> Self {
+		let silent = match (last_valid_observation_time, reference_time) {
+			(Some(last_valid), Some(reference)) => reference - last_valid > Duration::minutes(SILENT_AFTER_MINUTES),
+			_ => true,
+		};
+
+		if silent {
+			SensorStatus::Silent
+		} else if reading_count > 0 && bad_reading_count as f64 / reading_count as f64 >= DEGRADED_SHARE {
+			SensorStatus::Degraded
+		} else {
+			SensorStatus::Ok
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindSensorHealthParams {
+	pub equipment_number: Option<i32>,
+	pub bbox: Option<BoundingBox>,
+}
+
+#[derive(sqlx::FromRow)]
+struct LocationHealthRow {
+	location_id: i32,
+	descriptive_id: String,
+	full_name: String,
+	equipment_number: i32,
+	latitude: f64,
+	longitude: f64,
+	last_observation_time: Option<DateTime<Utc>>,
+	last_valid_observation_time: Option<DateTime<Utc>>,
+	reading_count: i64,
+	defective_count: i64,
+	unavailable_count: i64,
+}
+
+fn share(count: i64, total: i64) -> Option<f64> {
+	(total > 0).then(|| count as f64 / total as f64)
+}
+
+pub struct SensorHealth;
+
+impl SensorHealth {
+	/// Folds the readings observed since `since` into the latest observation times and
+	/// recounts the hours they fall in, so repeated runs over the same data are harmless
+	pub async fn update_since(
+		pool: &sqlx::PgPool,
+		since: DateTime<Utc>,
+	) -> Result<(), sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		let mut query = QueryBuilder::<Postgres>::new(
+			"INSERT INTO public.sensor_health (location_id, last_observation_time, last_valid_observation_time, updated_at)
+			SELECT
+				t.location_id,
+				MAX(t.observation_time),
+				MAX(t.observation_time) FILTER (WHERE TRUE"
+		);
+		query
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(SENSOR_ERROR_FILTER)
+			.push(
+				"),
+				NOW()
+			FROM public.traffic_measurements t
+			WHERE t.observation_time >= "
+			)
+			.push_bind(since)
+			.push(
+				" GROUP BY t.location_id
+			ON CONFLICT (location_id) DO UPDATE SET
+				last_observation_time = GREATEST(sensor_health.last_observation_time, EXCLUDED.last_observation_time),
+				last_valid_observation_time = GREATEST(sensor_health.last_valid_observation_time, EXCLUDED.last_valid_observation_time),
+				updated_at = EXCLUDED.updated_at"
+			);
+		query
+			.build()
+			.execute(&mut *tx)
+			.await?;
+
+		// Readings of an available sensor that fail the checks count as defective
+		let mut query = QueryBuilder::<Postgres>::new(
+			"INSERT INTO public.sensor_health_hourly (location_id, hour, reading_count, defective_count, unavailable_count)
+			SELECT
+				t.location_id,
+				time_bucket('1 hour', t.observation_time),
+				COUNT(*),
+				COUNT(*) FILTER (WHERE t.available IS NOT FALSE AND NOT (TRUE"
+		);
+		query
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(SENSOR_ERROR_FILTER)
+			.push(
+				")),
+				COUNT(*) FILTER (WHERE t.available IS FALSE)
+			FROM public.traffic_measurements t
+			WHERE t.observation_time >= time_bucket('1 hour', "
+			)
+			.push_bind(since)
+			.push(
+				"::TIMESTAMPTZ)
+			GROUP BY 1, 2
+			ON CONFLICT (location_id, hour) DO UPDATE SET
+				reading_count = EXCLUDED.reading_count,
+				defective_count = EXCLUDED.defective_count,
+				unavailable_count = EXCLUDED.unavailable_count"
+			);
+		query
+			.build()
+			.execute(&mut *tx)
+			.await?;
+
+		tx.commit().await
+	}
+
+	/// Latest observation of any location, the moment silence is measured against.
+	/// Using the network instead of the clock keeps a stalled feed from marking every sensor silent.
+	pub async fn get_reference_time(
+		pool: &sqlx::PgPool,
+	) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+		let row = sqlx::query!(
+			r#"SELECT MAX(last_observation_time) as reference_time FROM public.sensor_health"#
+		)
+		.fetch_one(pool)
+		.await?;
+
+		Ok(row.reference_time)
+	}
+
+	/// Health of every currently configured location, including the ones that never reported
+	pub async fn get_locations(
+		pool: &sqlx::PgPool,
+		params: FindSensorHealthParams,
+		reference_time: Option<DateTime<Utc>>,
+	) -> Result<Vec<LocationHealthDTO>, sqlx::Error> {
+		let window_start = reference_time.unwrap_or_else(Utc::now) - Duration::hours(HEALTH_WINDOW_HOURS);
+
+		let mut query = QueryBuilder::<Postgres>::new(
+			"SELECT
+				l.location_id,
+				l.descriptive_id,
+				l.full_name,
+				l.equipment_number,
+				l.latitude,
+				l.longitude,
+				h.last_observation_time,
+				h.last_valid_observation_time,
+				COALESCE(c.reading_count, 0) AS reading_count,
+				COALESCE(c.defective_count, 0) AS defective_count,
+				COALESCE(c.unavailable_count, 0) AS unavailable_count
+			FROM public.locations l
+			LEFT JOIN public.sensor_health h ON h.location_id = l.location_id
+			LEFT JOIN (
+				SELECT
+					location_id,
+					SUM(reading_count)::BIGINT AS reading_count,
+					SUM(defective_count)::BIGINT AS defective_count,
+					SUM(unavailable_count)::BIGINT AS unavailable_count
+				FROM public.sensor_health_hourly
+				WHERE hour >= time_bucket('1 hour', "
+		);
+		query
+			.push_bind(window_start)
+			.push(
+				"::TIMESTAMPTZ)
+				GROUP BY location_id
+			) c ON c.location_id = l.location_id
+			-- Removed sensors stop reporting, they are not silent
+			WHERE l.location_id IN (SELECT location_id FROM public.location_versions WHERE valid_to IS NULL)"
+			);
+
+		if let Some(equipment_number) = params.equipment_number {
+			query
+				.push(" AND l.equipment_number = ")
+				.push_bind(equipment_number);
+		}
+
+		if let Some(bbox) = params.bbox {
+			query
+				.push(" AND l.geog && ST_MakeEnvelope(")
+				.push_bind(bbox.min_lon)
+				.push(", ")
+				.push_bind(bbox.min_lat)
+				.push(", ")
+				.push_bind(bbox.max_lon)
+				.push(", ")
+				.push_bind(bbox.max_lat)
+				.push(", 4326)::geography");
+		}
+
+		query.push(" ORDER BY l.equipment_number, l.location_id");
+
+		let rows = query
+			.build_query_as::<LocationHealthRow>()
+			.fetch_all(pool)
+			.await?;
+
+		Ok(rows
+			.into_iter()
+			.map(|row| LocationHealthDTO {
+				status: SensorStatus::classify(
+					row.last_valid_observation_time,
+					reference_time,
+					row.reading_count,
+					row.defective_count + row.unavailable_count,
+				),
+				defective_share: share(row.defective_count, row.reading_count),
+				unavailable_share: share(row.unavailable_count, row.reading_count),
+				location_id: row.location_id,
+				descriptive_id: row.descriptive_id,
+				full_name: row.full_name,
+				equipment_number: row.equipment_number,
+				latitude: row.latitude,
+				longitude: row.longitude,
+				last_observation_time: row.last_observation_time,
+				last_valid_observation_time: row.last_valid_observation_time,
+				reading_count: row.reading_count,
+				defective_count: row.defective_count,
+				unavailable_count: row.unavailable_count,
+			})
+			.collect())
+	}
+
+	/// Rolls the location health up to the measuring stations. A station is silent when
+	/// all of its lanes are and degraded as soon as one of them is not OK.
+	pub fn group_by_equipment(
+		locations: &[LocationHealthDTO],
+		open_outages: &[EquipmentOutageDTO],
+	) -> Vec<EquipmentHealthDTO> {
+		let mut equipment: BTreeMap<i32, Vec<&LocationHealthDTO>> = BTreeMap::new();
+
+		for location in locations {
+			equipment.entry(location.equipment_number).or_default().push(location);
+		}
+
+		equipment
+			.into_iter()
+			.map(|(equipment_number, locations)| {
+				let count_status = |status| locations.iter().filter(|location| location.status == status).count();
+				let silent_count = count_status(SensorStatus::Silent);
+				let degraded_count = count_status(SensorStatus::Degraded);
+
+				let status = if silent_count == locations.len() {
+					SensorStatus::Silent
+				} else if silent_count + degraded_count > 0 {
+					SensorStatus::Degraded
+				} else {
+					SensorStatus::Ok
+				};
+
+				let reading_count = locations.iter().map(|location| location.reading_count).sum();
+
+				EquipmentHealthDTO {
+					equipment_number,
+					status,
+					location_count: locations.len(),
+					degraded_count,
+					silent_count,
+					last_valid_observation_time: locations.iter().filter_map(|location| location.last_valid_observation_time).max(),
+					reading_count,
+					defective_share: share(locations.iter().map(|location| location.defective_count).sum(), reading_count),
+					unavailable_share: share(locations.iter().map(|location| location.unavailable_count).sum(), reading_count),
+					outage_since: open_outages
+						.iter()
+						.find(|outage| outage.equipment_number == equipment_number)
+						.map(|outage| outage.started_at),
+				}
+			})
+			.collect()
+	}
+}
+
+pub struct EquipmentOutage;
+
+impl EquipmentOutage {
+	/// Opens an outage for every station whose lanes all went silent and closes the
+	/// outages of stations that produced a valid reading again or were removed from the
+	/// configuration. Returns the number of outages still open.
+	pub async fn detect(
+		pool: &sqlx::PgPool,
+	) -> Result<i64, sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		let mut query = QueryBuilder::<Postgres>::new(
+			"WITH equipment AS (
+				SELECT l.equipment_number, MAX(h.last_valid_observation_time) AS last_valid_observation_time
+				FROM public.locations l
+				LEFT JOIN public.sensor_health h ON h.location_id = l.location_id
+				WHERE l.location_id IN (SELECT location_id FROM public.location_versions WHERE valid_to IS NULL)
+				GROUP BY l.equipment_number
+			)
+			UPDATE public.equipment_outages o
+			-- First valid reading after the outage started, not the latest one
+			SET ended_at = COALESCE((
+				SELECT MIN(t.observation_time)
+				FROM public.locations l
+				INNER JOIN public.traffic_measurements t ON t.location_id = l.location_id
+				WHERE l.equipment_number = o.equipment_number
+					AND t.observation_time > o.started_at"
+		);
+		query
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(SENSOR_ERROR_FILTER)
+			.push(
+				"
+			), e.last_valid_observation_time)
+			FROM equipment e
+			WHERE o.ended_at IS NULL
+				AND o.equipment_number = e.equipment_number
+				AND e.last_valid_observation_time >= (SELECT MAX(last_observation_time) FROM public.sensor_health) - make_interval(mins => "
+			)
+			.push_bind(SILENT_AFTER_MINUTES as i32)
+			.push(")");
+		query
+			.build()
+			.execute(&mut *tx)
+			.await?;
+
+		// A station removed from the configuration is gone, not broken
+		sqlx::query!(
+			r#"
+			UPDATE public.equipment_outages o
+			SET ended_at = GREATEST(o.started_at, (
+				SELECT MAX(v.valid_to)
+				FROM public.location_versions v
+				WHERE v.equipment_number = o.equipment_number
+			))
+			WHERE o.ended_at IS NULL
+				AND NOT EXISTS (
+					SELECT 1
+					FROM public.location_versions v
+					WHERE v.equipment_number = o.equipment_number
+						AND v.valid_to IS NULL
+				)
+			"#
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		sqlx::query!(
+			r#"
+			WITH reference AS (
+				SELECT MAX(last_observation_time) AS reference_time FROM public.sensor_health
+			),
+			equipment AS (
+				SELECT l.equipment_number, MAX(h.last_valid_observation_time) AS last_valid_observation_time
+				FROM public.locations l
+				LEFT JOIN public.sensor_health h ON h.location_id = l.location_id
+				WHERE l.location_id IN (SELECT location_id FROM public.location_versions WHERE valid_to IS NULL)
+				GROUP BY l.equipment_number
+			)
+			INSERT INTO public.equipment_outages (equipment_number, started_at, detected_at)
+			SELECT e.equipment_number, COALESCE(e.last_valid_observation_time, r.reference_time), NOW()
+			FROM equipment e, reference r
+			WHERE r.reference_time IS NOT NULL
+				AND (e.last_valid_observation_time IS NULL
+					OR e.last_valid_observation_time < r.reference_time - make_interval(mins => $1))
+			ON CONFLICT (equipment_number) WHERE ended_at IS NULL DO NOTHING
+			"#,
+			SILENT_AFTER_MINUTES as i32
+		)
+		.execute(&mut *tx)
+		.await?;
+
+		let row = sqlx::query!(
+			r#"SELECT COUNT(*) as "count!" FROM public.equipment_outages WHERE ended_at IS NULL"#
+		)
+		.fetch_one(&mut *tx)
+		.await?;
+
+		tx.commit().await?;
+
+		Ok(row.count)
+	}
+
+	pub async fn get_open(
+		pool: &sqlx::PgPool,
+	) -> Result<Vec<EquipmentOutageDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			EquipmentOutageDTO,
+			r#"
+			SELECT outage_id, equipment_number, started_at, detected_at, ended_at
+			FROM public.equipment_outages
+			WHERE ended_at IS NULL
+			ORDER BY started_at
+			"#
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Outages overlapping the time range, newest first
+	pub async fn get_history(
+		pool: &sqlx::PgPool,
+		equipment_number: Option<i32>,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<EquipmentOutageDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			EquipmentOutageDTO,
+			r#"
+			SELECT outage_id, equipment_number, started_at, detected_at, ended_at
+			FROM public.equipment_outages
+			WHERE started_at < $2
+				AND (ended_at IS NULL OR ended_at >= $1)
+				AND ($3::INTEGER IS NULL OR equipment_number = $3)
+			ORDER BY started_at DESC
+			"#,
+			from, to, equipment_number
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn location(location_id: i32, equipment_number: i32, status: SensorStatus, reading_count: i64, defective_count: i64) -> LocationHealthDTO {
+		LocationHealthDTO {
+			location_id,
+			descriptive_id: format!("H{}", location_id),
+			full_name: String::new(),
+			equipment_number,
+			latitude: 51.0,
+			longitude: 4.0,
+			status,
+			last_observation_time: None,
+			last_valid_observation_time: DateTime::from_timestamp(1_733_130_000 + location_id as i64, 0),
+			reading_count,
+			defective_count,
+			unavailable_count: 0,
+			defective_share: None,
+			unavailable_share: None,
+		}
+	}
+
+	#[test]
+	fn classify_is_silent_when_the_last_valid_reading_lags_behind() {
+		let reference = Utc::now();
+
+		assert_eq!(SensorStatus::classify(Some(reference - Duration::minutes(SILENT_AFTER_MINUTES + 1)), Some(reference), 100, 0), SensorStatus::Silent);
+		assert_eq!(SensorStatus::classify(Some(reference - Duration::minutes(SILENT_AFTER_MINUTES)), Some(reference), 100, 0), SensorStatus::Ok);
+	}
+
+	#[test]
+	fn classify_is_silent_without_a_valid_reading_or_reference() {
+		let reference = Utc::now();
+
+		assert_eq!(SensorStatus::classify(None, Some(reference), 100, 0), SensorStatus::Silent);
+		assert_eq!(SensorStatus::classify(Some(reference), None, 100, 0), SensorStatus::Silent);
+	}
+
+	#[test]
+	fn classify_is_degraded_from_the_bad_reading_share() {
+		let reference = Utc::now();
+
+		assert_eq!(SensorStatus::classify(Some(reference), Some(reference), 100, 20), SensorStatus::Degraded);
+		assert_eq!(SensorStatus::classify(Some(reference), Some(reference), 100, 19), SensorStatus::Ok);
+		assert_eq!(SensorStatus::classify(Some(reference), Some(reference), 0, 0), SensorStatus::Ok);
+	}
+
+	#[test]
+	fn group_by_equipment_rolls_lanes_up_per_station() {
+		let locations = [
+			location(1, 10, SensorStatus::Ok, 100, 0),
+			location(2, 10, SensorStatus::Ok, 100, 10),
+			location(3, 20, SensorStatus::Ok, 50, 0),
+			location(4, 20, SensorStatus::Silent, 50, 0),
+			location(5, 30, SensorStatus::Silent, 0, 0),
+		];
+
+		let equipment = SensorHealth::group_by_equipment(&locations, &[]);
+
+		let statuses: Vec<(i32, SensorStatus)> = equipment.iter().map(|station| (station.equipment_number, station.status)).collect();
+		assert_eq!(statuses, [(10, SensorStatus::Ok), (20, SensorStatus::Degraded), (30, SensorStatus::Silent)]);
+
+		let station = &equipment[0];
+		assert_eq!(station.location_count, 2);
+		assert_eq!(station.reading_count, 200);
+		assert_eq!(station.defective_share, share(10, 200));
+		assert_eq!(station.last_valid_observation_time, locations[1].last_valid_observation_time);
+		assert_eq!((equipment[1].silent_count, equipment[1].degraded_count), (1, 0));
+	}
+
+	#[test]
+	fn group_by_equipment_reports_the_open_outage() {
+		let started_at = DateTime::from_timestamp(1_733_130_000, 0).unwrap();
+		let outage = EquipmentOutageDTO {
+			outage_id: 1,
+			equipment_number: 30,
+			started_at,
+			detected_at: started_at,
+			ended_at: None,
+		};
+
+		let equipment = SensorHealth::group_by_equipment(
+			&[location(1, 10, SensorStatus::Ok, 10, 0), location(5, 30, SensorStatus::Silent, 0, 0)],
+			&[outage],
+		);
+
+		assert_eq!(equipment[0].outage_since, None);
+		assert_eq!(equipment[1].outage_since, Some(started_at));
+	}
+}
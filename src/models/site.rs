@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::dto::site::{ContourCellDTO, CorridorSiteDTO, SiteDTO, SiteMeasurementDTO};
+
+use super::location::BoundingBox;
+
+/// Direction of travel relative to the kilometre points of the road
+#[derive(sqlx::Type, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
+#[sqlx(type_name = "road_direction", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RoadDirection {
+	Positive,
+	Negative,
+}
+
+const SELECT_SITES: &str = r#"
+	SELECT
+		s.site_id,
+		s.equipment_number,
+		s.ident_8,
+		s.road,
+		s.direction,
+		s.main_carriageway,
+		s.full_name,
+		s.kilometer_point,
+		s.latitude,
+		s.longitude,
+		s.lane_count,
+		s.location_ids,
+		s.lanes
+	FROM public.measuring_sites s
+"#;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindSitesParams {
+	pub road: Option<String>,
+	pub direction: Option<RoadDirection>,
+	pub bbox: Option<BoundingBox>,
+}
+
+/// Lanes grouped by equipment and carriageway
+pub struct Site;
+
+impl Site {
+	pub async fn get_all(
+		pool: &sqlx::PgPool,
+		params: FindSitesParams,
+	) -> Result<Vec<SiteDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(SELECT_SITES);
+		query.push(" WHERE TRUE");
+
+		if let Some(road) = params.road {
+			query
+				.push(" AND s.road = ")
+				.push_bind(road.to_uppercase());
+		}
+
+		if let Some(direction) = params.direction {
+			query
+				.push(" AND s.direction = ")
+				.push_bind(direction);
+		}
+
+		// Sites with a lane in the box, looked up on the indexed location geography
+		if let Some(bbox) = params.bbox {
+			query
+				.push(" AND (s.equipment_number, s.ident_8) IN (SELECT l.equipment_number, l.ident_8 FROM public.locations l WHERE l.geog && ST_MakeEnvelope(")
+				.push_bind(bbox.min_lon)
+				.push(", ")
+				.push_bind(bbox.min_lat)
+				.push(", ")
+				.push_bind(bbox.max_lon)
+				.push(", ")
+				.push_bind(bbox.max_lat)
+				.push(", 4326)::geography)");
+		}
+
+		query.push(" ORDER BY s.road, s.direction, s.kilometer_point, s.site_id");
+
+		query
+			.build_query_as::<SiteDTO>()
+			.fetch_all(pool)
+			.await
+	}
+
+	pub async fn get_by_id(
+		pool: &sqlx::PgPool,
+		site_id: &str,
+	) -> Result<Option<SiteDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(SELECT_SITES);
+		query
+			.push(" WHERE s.site_id = ")
+			.push_bind(site_id);
+
+		query
+			.build_query_as::<SiteDTO>()
+			.fetch_optional(pool)
+			.await
+	}
+
+	/// Readings of the lanes of a site combined per observation time. Counts are summed,
+	/// speeds weighted by the lane intensities and occupancy averaged over the lanes.
+	pub async fn get_measurements(
+		pool: &sqlx::PgPool,
+		location_ids: &[i32],
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<SiteMeasurementDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			SiteMeasurementDTO,
+			r#"
+			SELECT
+				t.observation_time,
+				COUNT(*)::INTEGER as "lane_count!",
+				SUM(t.total_vehicles_passed)::BIGINT as total_vehicles_passed,
+				SUM(t.total_vehicles_passed * t.time_mean_speed)
+					/ NULLIF(SUM(t.total_vehicles_passed) FILTER (WHERE t.time_mean_speed IS NOT NULL), 0) as time_mean_speed,
+				SUM(t.total_vehicles_passed) FILTER (WHERE t.space_mean_speed > 0)
+					/ NULLIF(SUM(t.total_vehicles_passed / t.space_mean_speed) FILTER (WHERE t.space_mean_speed > 0), 0) as space_mean_speed,
+				AVG(t.occupancy_rate)::DOUBLE PRECISION as occupancy_rate
+			FROM public.traffic_measurements t
+			WHERE t.location_id = ANY($1)
+				AND t.observation_time >= $2
+				AND t.observation_time < $3
+				AND is_valid_measurement(t.valid, t.available, t.faulty, t.plausibility_flags)
+			GROUP BY t.observation_time
+			ORDER BY t.observation_time
+			"#,
+			location_ids, from, to
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Main carriageway sites of a road in driving order, with their latest readings
+	/// combined across lanes. Readings older than `since` are left out.
+	pub async fn get_corridor(
+		pool: &sqlx::PgPool,
+		road: &str,
+		direction: RoadDirection,
+		since: DateTime<Utc>,
+	) -> Result<Vec<CorridorSiteDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			CorridorSiteDTO,
+			r#"
+			SELECT
+				s.site_id as "site_id!",
+				s.full_name as "full_name!",
+				s.kilometer_point as "kilometer_point!",
+				s.latitude as "latitude!",
+				s.longitude as "longitude!",
+				s.lane_count as "lane_count!",
+				c.observation_time,
+				c.reporting_lane_count as "reporting_lane_count!",
+				c.total_vehicles_passed,
+				c.time_mean_speed,
+				c.space_mean_speed,
+				c.occupancy_rate
+			FROM public.measuring_sites s
+			CROSS JOIN LATERAL (
+				SELECT
+					MAX(t.observation_time) as observation_time,
+					COUNT(*)::INTEGER as reporting_lane_count,
+					SUM(t.total_vehicles_passed)::BIGINT as total_vehicles_passed,
+					SUM(t.total_vehicles_passed * t.time_mean_speed)
+						/ NULLIF(SUM(t.total_vehicles_passed) FILTER (WHERE t.time_mean_speed IS NOT NULL), 0) as time_mean_speed,
+					SUM(t.total_vehicles_passed) FILTER (WHERE t.space_mean_speed > 0)
+						/ NULLIF(SUM(t.total_vehicles_passed / t.space_mean_speed) FILTER (WHERE t.space_mean_speed > 0), 0) as space_mean_speed,
+					AVG(t.occupancy_rate)::DOUBLE PRECISION as occupancy_rate
+				FROM public.current_traffic_measurements t
+				WHERE t.location_id = ANY(s.location_ids)
+					AND t.observation_time >= $3
+					AND is_valid_measurement(t.valid, t.available, t.faulty, t.plausibility_flags)
+			) c
+			WHERE s.road = $1 AND s.direction = $2
+				AND s.main_carriageway
+				AND s.kilometer_point IS NOT NULL
+			ORDER BY CASE WHEN s.direction = 'POSITIVE' THEN s.kilometer_point ELSE -s.kilometer_point END
+			"#,
+			road, direction as RoadDirection, since
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Space-mean speed per main carriageway site of a road and `bucket_minutes` bucket
+	pub async fn get_speed_contour(
+		pool: &sqlx::PgPool,
+		road: &str,
+		direction: RoadDirection,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+		bucket_minutes: i32,
+	) -> Result<Vec<ContourCellDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			ContourCellDTO,
+			r#"
+			SELECT
+				s.site_id as "site_id!",
+				time_bucket(make_interval(mins => $5), t.observation_time) as "bucket!",
+				SUM(t.total_vehicles_passed) FILTER (WHERE t.space_mean_speed > 0)
+					/ NULLIF(SUM(t.total_vehicles_passed / t.space_mean_speed) FILTER (WHERE t.space_mean_speed > 0), 0) as space_mean_speed
+			FROM public.measuring_sites s
+			INNER JOIN public.traffic_measurements t ON t.location_id = ANY(s.location_ids)
+			WHERE s.road = $1 AND s.direction = $2
+				AND s.main_carriageway
+				AND s.kilometer_point IS NOT NULL
+				AND t.observation_time >= $3
+				AND t.observation_time < $4
+				AND is_valid_measurement(t.valid, t.available, t.faulty, t.plausibility_flags)
+			GROUP BY 1, 2
+			ORDER BY 2
+			"#,
+			road, direction as RoadDirection, from, to, bucket_minutes
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+/// Compression and retention settings for a hypertable or continuous aggregate.
+/// `None` removes the policy, periods are in days.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoragePolicy {
+	pub relation: String,
+	// Only relations with compression enabled can have a compression policy
+	pub compressed: bool,
+	pub compress_after_days: Option<i32>,
+	pub drop_after_days: Option<i32>,
+}
+
+impl StoragePolicy {
+	/// Days the refresh policy of the continuous aggregate `view_name` reaches back into
+	/// the relation it is built from, `None` without a bounded refresh policy
+	pub async fn refresh_start_offset_days(
+		pool: &sqlx::PgPool,
+		view_name: &str,
+	) -> Result<Option<f64>, sqlx::Error> {
+		let days = sqlx::query_scalar::<_, Option<f64>>(
+			"SELECT EXTRACT(EPOCH FROM (j.config->>'start_offset')::INTERVAL)::DOUBLE PRECISION / 86400
+			FROM timescaledb_information.jobs j
+			INNER JOIN timescaledb_information.continuous_aggregates c
+				ON c.materialization_hypertable_schema = j.hypertable_schema
+				AND c.materialization_hypertable_name = j.hypertable_name
+			WHERE j.proc_name = 'policy_refresh_continuous_aggregate'
+				AND c.view_name = $1"
+		)
+			.bind(view_name)
+			.fetch_optional(pool)
+			.await?;
+
+		Ok(days.flatten())
+	}
+
+	/// Replaces the TimescaleDB jobs of the relation with the configured ones
+	pub async fn apply(
+		&self,
+		pool: &sqlx::PgPool,
+	) -> Result<(), sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		if self.compressed {
+			sqlx::query("SELECT remove_compression_policy($1::regclass, if_exists => true)")
+				.bind(&self.relation)
+				.execute(&mut *tx)
+				.await?;
+		}
+
+		if let (true, Some(compress_after_days)) = (self.compressed, self.compress_after_days) {
+			sqlx::query("SELECT add_compression_policy($1::regclass, compress_after => make_interval(days => $2))")
+				.bind(&self.relation)
+				.bind(compress_after_days)
+				.execute(&mut *tx)
+				.await?;
+		}
+
+		sqlx::query("SELECT remove_retention_policy($1::regclass, if_exists => true)")
+			.bind(&self.relation)
+			.execute(&mut *tx)
+			.await?;
+
+		if let Some(drop_after_days) = self.drop_after_days {
+			sqlx::query("SELECT add_retention_policy($1::regclass, drop_after => make_interval(days => $2))")
+				.bind(&self.relation)
+				.bind(drop_after_days)
+				.execute(&mut *tx)
+				.await?;
+		}
+
+		tx.commit().await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::dto::aggregate::AggregateDTO;
+
+/// Granularity of the continuous aggregates on `traffic_measurements`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AggregateBucket {
+	#[serde(rename = "15m")]
+	FifteenMinutes,
+	#[serde(rename = "1h")]
+	Hour,
+	#[serde(rename = "1d")]
+	Day,
+}
+
+impl AggregateBucket {
+	pub fn view_name(&self) -> &'static str {
+		match self {
+			AggregateBucket::FifteenMinutes => "public.traffic_measurements_15m",
+			AggregateBucket::Hour => "public.traffic_measurements_hourly",
+			AggregateBucket::Day => "public.traffic_measurements_daily",
+		}
+	}
+
+	pub fn duration(&self) -> Duration {
+		match self {
+			AggregateBucket::FifteenMinutes => Duration::minutes(15),
+			AggregateBucket::Hour => Duration::hours(1),
+			AggregateBucket::Day => Duration::days(1),
+		}
+	}
+
+	/// Range returned when the request has no `from`
+	pub fn default_range(&self) -> Duration {
+		match self {
+			AggregateBucket::FifteenMinutes => Duration::days(1),
+			AggregateBucket::Hour => Duration::days(7),
+			AggregateBucket::Day => Duration::days(90),
+		}
+	}
+
+	/// Largest range that can be requested, keeps responses to a few thousand buckets
+	pub fn max_range(&self) -> Duration {
+		match self {
+			AggregateBucket::FifteenMinutes => Duration::days(31),
+			AggregateBucket::Hour => Duration::days(366),
+			AggregateBucket::Day => Duration::days(3660),
+		}
+	}
+}
+
+/// Resolution of a measurement series, from the raw one-minute readings up to daily aggregates
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Resolution {
+	#[serde(rename = "raw")]
+	Raw,
+	#[serde(rename = "5m")]
+	FiveMinutes,
+	#[serde(rename = "15m")]
+	FifteenMinutes,
+	#[serde(rename = "1h")]
+	Hour,
+	#[serde(rename = "1d")]
+	Day,
+}
+
+impl Resolution {
+	const ALL: [Resolution; 5] = [
+		Resolution::Raw,
+		Resolution::FiveMinutes,
+		Resolution::FifteenMinutes,
+		Resolution::Hour,
+		Resolution::Day,
+	];
+
+	pub fn duration(&self) -> Duration {
+		match self {
+			Resolution::Raw => Duration::minutes(1),
+			Resolution::FiveMinutes => Duration::minutes(5),
+			Resolution::FifteenMinutes => Duration::minutes(15),
+			Resolution::Hour => Duration::hours(1),
+			Resolution::Day => Duration::days(1),
+		}
+	}
+
+	/// The continuous aggregate backing this resolution, if there is one
+	pub fn aggregate_bucket(&self) -> Option<AggregateBucket> {
+		match self {
+			Resolution::Raw | Resolution::FiveMinutes => None,
+			Resolution::FifteenMinutes => Some(AggregateBucket::FifteenMinutes),
+			Resolution::Hour => Some(AggregateBucket::Hour),
+			Resolution::Day => Some(AggregateBucket::Day),
+		}
+	}
+
+	/// Number of points a series over `range` has at this resolution
+	pub fn point_count(&self, range: Duration) -> i64 {
+		let step = self.duration().num_seconds();
+		(range.num_seconds() + step - 1) / step
+	}
+
+	/// Picks the finest resolution that keeps a series over `range` within `max_points`,
+	/// falling back to daily aggregates for very long ranges
+	pub fn select(range: Duration, max_points: i64) -> Resolution {
+		Resolution::ALL
+			.into_iter()
+			.find(|resolution| resolution.point_count(range) <= max_points)
+			.unwrap_or(Resolution::Day)
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindAggregatesParams {
+	pub bucket: AggregateBucket,
+	pub from: DateTime<Utc>,
+	pub to: DateTime<Utc>,
+}
+
+pub struct TrafficAggregate;
+
+impl TrafficAggregate {
+	/// Materializes every bucket the refresh policies have not, finest level first so the
+	/// coarser levels are built on complete data. Buckets that are already materialized and
+	/// not invalidated since are left alone. Cannot run inside a transaction.
+	pub async fn refresh_all(
+		pool: &sqlx::PgPool,
+	) -> Result<(), sqlx::Error> {
+		for bucket in [AggregateBucket::FifteenMinutes, AggregateBucket::Hour, AggregateBucket::Day] {
+			let query = format!("CALL refresh_continuous_aggregate('{}', NULL, NULL)", bucket.view_name());
+
+			sqlx::raw_sql(&query)
+				.execute(pool)
+				.await?;
+		}
+
+		Ok(())
+	}
+
+	pub async fn get_by_location_id(
+		pool: &sqlx::PgPool,
+		location_id: i32,
+		params: FindAggregatesParams,
+	) -> Result<Vec<AggregateDTO>, sqlx::Error> {
+		let expected_samples = params.bucket.duration().num_minutes() as f64;
+
+		let mut query = QueryBuilder::<Postgres>::new(
+			"SELECT
+				location_id,
+				bucket,
+				total_vehicles_passed,
+				time_mean_speed_sum / NULLIF(time_mean_vehicles, 0) AS time_mean_speed,
+				space_mean_vehicles / NULLIF(inverse_speed_sum, 0) AS space_mean_speed,
+				occupancy_rate_sum::DOUBLE PRECISION / NULLIF(occupancy_rate_count, 0) AS occupancy_rate,
+				sample_count::DOUBLE PRECISION / "
+		);
+		query
+			.push_bind(expected_samples)
+			.push(" AS coverage, sample_count FROM ")
+			.push(params.bucket.view_name())
+			.push(" WHERE location_id = ")
+			.push_bind(location_id)
+			.push(" AND bucket >= ")
+			.push_bind(params.from)
+			.push(" AND bucket < ")
+			.push_bind(params.to)
+			.push(" ORDER BY bucket");
+
+		query
+			.build_query_as::<AggregateDTO>()
+			.fetch_all(pool)
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn point_count_rounds_partial_steps_up() {
+		assert_eq!(Resolution::Raw.point_count(Duration::minutes(60)), 60);
+		assert_eq!(Resolution::FifteenMinutes.point_count(Duration::minutes(61)), 5);
+		assert_eq!(Resolution::Day.point_count(Duration::zero()), 0);
+	}
+
+	#[test]
+	fn select_picks_the_finest_resolution_within_the_limit() {
+		assert_eq!(Resolution::select(Duration::hours(1), 1000), Resolution::Raw);
+		assert_eq!(Resolution::select(Duration::days(1), 1000), Resolution::FiveMinutes);
+		assert_eq!(Resolution::select(Duration::days(7), 1000), Resolution::FifteenMinutes);
+		assert_eq!(Resolution::select(Duration::days(30), 1000), Resolution::Hour);
+		assert_eq!(Resolution::select(Duration::days(365), 1000), Resolution::Day);
+	}
+
+	#[test]
+	fn select_keeps_a_resolution_that_exactly_fits() {
+		assert_eq!(Resolution::select(Duration::minutes(1000), 1000), Resolution::Raw);
+		assert_eq!(Resolution::select(Duration::minutes(1001), 1000), Resolution::FiveMinutes);
+	}
+
+	#[test]
+	fn select_falls_back_to_daily_aggregates() {
+		assert_eq!(Resolution::select(Duration::days(5000), 1000), Resolution::Day);
+	}
+
+	#[test]
+	fn only_coarse_resolutions_read_an_aggregate() {
+		assert_eq!(Resolution::Raw.aggregate_bucket(), None);
+		assert_eq!(Resolution::FiveMinutes.aggregate_bucket(), None);
+		assert_eq!(Resolution::Hour.aggregate_bucket(), Some(AggregateBucket::Hour));
+	}
+}
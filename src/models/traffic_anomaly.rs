@@ -0,0 +1,297 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::dto::anomaly::AnomalyDTO;
+
+use super::{location::BoundingBox, traffic_measurement::VALID_MEASUREMENTS_FILTER};
+
+#[derive(sqlx::Type, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
+#[sqlx(type_name = "anomaly_metric", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AnomalyMetric {
+	Intensity,
+	Speed,
+	Occupancy,
+}
+
+// Profile slots need this many days behind them to be trusted
+const MIN_PROFILE_SAMPLES: i32 = 4;
+// Valid readings the 15 minutes up to a reading need before it is compared with the profile
+const MIN_WINDOW_SAMPLES: i64 = 10;
+// Minutes without a deviating reading after which an anomaly is over
+const ANOMALY_GAP_MINUTES: i32 = 5;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindAnomaliesParams {
+	pub lat: Option<f64>,
+	pub lon: Option<f64>,
+	// Radius in meters around lat/lon
+	pub radius: f64,
+	pub bbox: Option<BoundingBox>,
+	pub from: Option<DateTime<Utc>>,
+	pub to: Option<DateTime<Utc>>,
+	pub metric: Option<AnomalyMetric>,
+	pub min_severity: f64,
+	pub limit: i64,
+}
+
+pub struct TrafficAnomaly;
+
+impl TrafficAnomaly {
+	/// Compares the measurements observed since `since` with the profile slot of their
+	/// location and records the locations that deviate at least `min_severity` standard
+	/// deviations. The profiles describe 15 minute buckets, so each reading is judged by
+	/// the valid readings of the 15 minutes up to it rather than by its own noisy minute,
+	/// against the slot that window starts in.
+	/// The spread is estimated from the 10th-90th percentile band, with a floor so quiet
+	/// or very regular slots do not flag every small change.
+	/// A deviation that lasts extends the open anomaly of its location and metric, which
+	/// is closed once no reading deviated for `ANOMALY_GAP_MINUTES`.
+	/// Returns the number of anomalies started or extended.
+	pub async fn detect_since(
+		pool: &sqlx::PgPool,
+		since: DateTime<Utc>,
+		time_zone: &str,
+		min_severity: f64,
+	) -> Result<u64, sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		// Deviations that stopped before this run can not be extended anymore
+		sqlx::query(
+			"UPDATE public.traffic_anomalies
+			SET ended_at = last_seen_at
+			WHERE ended_at IS NULL
+				AND last_seen_at < $1 - make_interval(mins => $2)"
+		)
+			.bind(since)
+			.bind(ANOMALY_GAP_MINUTES)
+			.execute(&mut *tx)
+			.await?;
+
+		let mut query = QueryBuilder::<Postgres>::new(
+			"WITH deviations AS (
+				SELECT
+					t.location_id,
+					t.observation_time,
+					v.metric,
+					v.observed_value,
+					v.expected_value,
+					v.lower_bound,
+					v.upper_bound,
+					ABS(v.observed_value - v.expected_value) / v.spread AS severity
+				FROM public.traffic_measurements t
+				CROSS JOIN LATERAL (
+					SELECT
+						-- Vehicles per 15 minutes, scaled up over missing minutes
+						(SUM(w.total_vehicles_passed) * 15.0 / COUNT(*))::DOUBLE PRECISION AS intensity,
+						-- The profiles are built from the time-mean speed
+						SUM(w.total_vehicles_passed * w.time_mean_speed)
+							/ NULLIF(SUM(w.total_vehicles_passed) FILTER (WHERE w.time_mean_speed IS NOT NULL), 0) AS time_mean_speed,
+						AVG(w.occupancy_rate)::DOUBLE PRECISION AS occupancy_rate,
+						COUNT(*) AS sample_count,
+						-- First minute of the window
+						t.observation_time - INTERVAL '14 minutes' AS window_start
+					FROM public.traffic_measurements w
+					WHERE w.location_id = t.location_id
+						AND w.observation_time > t.observation_time - INTERVAL '15 minutes'
+						AND w.observation_time <= t.observation_time
+						AND is_valid_measurement(w.valid, w.available, w.faulty, w.plausibility_flags)
+				) r
+				INNER JOIN public.traffic_profiles p ON p.location_id = t.location_id
+					AND p.weekday = EXTRACT(ISODOW FROM r.window_start AT TIME ZONE "
+		);
+		query
+			.push_bind(time_zone)
+			.push(
+				")::SMALLINT
+					AND p.slot_start = date_bin(INTERVAL '15 minutes', r.window_start AT TIME ZONE "
+			)
+			.push_bind(time_zone)
+			.push(
+				", TIMESTAMP '2000-01-01')::TIME
+				CROSS JOIN LATERAL (VALUES
+					-- Counts are at least as spread as a Poisson process
+					(
+						'INTENSITY'::anomaly_metric,
+						r.intensity,
+						p.intensity_p50,
+						p.intensity_p10,
+						p.intensity_p90,
+						GREATEST((p.intensity_p90 - p.intensity_p10) / 2.563, SQRT(GREATEST(p.intensity_p50, 1)))
+					),
+					(
+						'SPEED'::anomaly_metric,
+						r.time_mean_speed,
+						p.speed_p50,
+						p.speed_p10,
+						p.speed_p90,
+						GREATEST((p.speed_p90 - p.speed_p10) / 2.563, 5)
+					),
+					(
+						'OCCUPANCY'::anomaly_metric,
+						r.occupancy_rate,
+						p.occupancy_p50,
+						p.occupancy_p10,
+						p.occupancy_p90,
+						GREATEST((p.occupancy_p90 - p.occupancy_p10) / 2.563, 2)
+					)
+				) v(metric, observed_value, expected_value, lower_bound, upper_bound, spread)
+				WHERE t.observation_time >= "
+			)
+			.push_bind(since)
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(" AND p.sample_count >= ")
+			.push_bind(MIN_PROFILE_SAMPLES)
+			.push(" AND r.sample_count >= ")
+			.push_bind(MIN_WINDOW_SAMPLES)
+			.push(
+				" AND v.observed_value IS NOT NULL
+					AND v.expected_value IS NOT NULL
+					AND ABS(v.observed_value - v.expected_value) / v.spread >= "
+			)
+			.push_bind(min_severity)
+			.push(
+				"
+			),
+			-- A run only sees the last few minutes, so its deviations form one period per
+			-- location and metric, described by its most severe reading
+			periods AS (
+				SELECT DISTINCT ON (location_id, metric)
+					location_id,
+					metric,
+					MIN(observation_time) OVER (PARTITION BY location_id, metric) AS started_at,
+					MAX(observation_time) OVER (PARTITION BY location_id, metric) AS last_seen_at,
+					observed_value,
+					expected_value,
+					lower_bound,
+					upper_bound,
+					severity
+				FROM deviations
+				ORDER BY location_id, metric, severity DESC
+			)
+			INSERT INTO public.traffic_anomalies (
+				location_id,
+				metric,
+				started_at,
+				last_seen_at,
+				observed_value,
+				expected_value,
+				lower_bound,
+				upper_bound,
+				severity
+			)
+			SELECT
+				location_id,
+				metric,
+				started_at,
+				last_seen_at,
+				observed_value,
+				expected_value,
+				lower_bound,
+				upper_bound,
+				severity
+			FROM periods
+			ON CONFLICT (location_id, metric) WHERE ended_at IS NULL DO UPDATE SET
+				started_at = LEAST(traffic_anomalies.started_at, EXCLUDED.started_at),
+				last_seen_at = GREATEST(traffic_anomalies.last_seen_at, EXCLUDED.last_seen_at),
+				observed_value = CASE WHEN EXCLUDED.severity > traffic_anomalies.severity
+					THEN EXCLUDED.observed_value ELSE traffic_anomalies.observed_value END,
+				expected_value = CASE WHEN EXCLUDED.severity > traffic_anomalies.severity
+					THEN EXCLUDED.expected_value ELSE traffic_anomalies.expected_value END,
+				lower_bound = CASE WHEN EXCLUDED.severity > traffic_anomalies.severity
+					THEN EXCLUDED.lower_bound ELSE traffic_anomalies.lower_bound END,
+				upper_bound = CASE WHEN EXCLUDED.severity > traffic_anomalies.severity
+					THEN EXCLUDED.upper_bound ELSE traffic_anomalies.upper_bound END,
+				severity = GREATEST(traffic_anomalies.severity, EXCLUDED.severity)"
+			);
+
+		let result = query
+			.build()
+			.execute(&mut *tx)
+			.await?;
+
+		tx.commit().await?;
+
+		Ok(result.rows_affected())
+	}
+
+	pub async fn get_all(
+		pool: &sqlx::PgPool,
+		params: FindAnomaliesParams,
+	) -> Result<Vec<AnomalyDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(
+			"SELECT
+				a.location_id,
+				a.metric,
+				a.started_at,
+				a.last_seen_at,
+				a.ended_at,
+				a.observed_value,
+				a.expected_value,
+				a.lower_bound,
+				a.upper_bound,
+				a.severity,
+				a.detected_at,
+				l.descriptive_id,
+				l.full_name,
+				l.latitude,
+				l.longitude
+			FROM public.traffic_anomalies a
+			LEFT JOIN public.locations l ON a.location_id = l.location_id
+			WHERE a.severity >= "
+		);
+		query.push_bind(params.min_severity);
+
+		if let (Some(lat), Some(lon)) = (params.lat, params.lon) {
+			query
+				.push(" AND ST_DWithin(l.geog, ST_SetSRID(ST_MakePoint(")
+				.push_bind(lon)
+				.push(", ")
+				.push_bind(lat)
+				.push("), 4326)::geography, ")
+				.push_bind(params.radius)
+				.push(")");
+		}
+
+		if let Some(bbox) = params.bbox {
+			query
+				.push(" AND l.geog && ST_MakeEnvelope(")
+				.push_bind(bbox.min_lon)
+				.push(", ")
+				.push_bind(bbox.min_lat)
+				.push(", ")
+				.push_bind(bbox.max_lon)
+				.push(", ")
+				.push_bind(bbox.max_lat)
+				.push(", 4326)::geography");
+		}
+
+		if let Some(from) = params.from {
+			query
+				.push(" AND a.last_seen_at >= ")
+				.push_bind(from);
+		}
+
+		if let Some(to) = params.to {
+			query
+				.push(" AND a.started_at < ")
+				.push_bind(to);
+		}
+
+		if let Some(metric) = params.metric {
+			query
+				.push(" AND a.metric = ")
+				.push_bind(metric);
+		}
+
+		query
+			.push(" ORDER BY a.started_at DESC, a.severity DESC LIMIT ")
+			.push_bind(params.limit);
+
+		query
+			.build_query_as::<AnomalyDTO>()
+			.fetch_all(pool)
+			.await
+	}
+}
@@ -1,7 +1,56 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
 
-use crate::dto::measurement::MeasurementDTO;
+use crate::dto::{aggregate::AggregateDTO, cursor::MeasurementCursor, measurement::MeasurementDTO};
+
+/// Measurements joined with the location configuration that was valid at the time of observation
+const SELECT_MEASUREMENTS: &str = r#"
+	SELECT
+		t.location_id,
+		t.observation_time,
+		t.occupancy_rate,
+		t.availability_rate,
+		t.instability,
+		t.total_vehicles_passed,
+		t.average_speed,
+		t.max_speed,
+		t.time_mean_speed,
+		t.space_mean_speed,
+		t.data_quality,
+		t.current_publication,
+		t.available,
+		t.faulty,
+		t.valid,
+		t.plausibility_flags,
+		f.free_flow_speed,
+		classify_congestion(
+			COALESCE(t.time_mean_speed, t.average_speed),
+			t.occupancy_rate,
+			t.total_vehicles_passed,
+			f.free_flow_speed
+		) AS congestion_level,
+		l.descriptive_id,
+		l.full_name,
+		l.ident_8,
+		l.equipment_number,
+		l.kilometer_point,
+		l.lane,
+		l.lambert_x,
+		l.lambert_y,
+		l.latitude,
+		l.longitude
+	FROM public.traffic_measurements t
+	LEFT JOIN public.location_versions l ON t.location_id = l.location_id
+		AND t.observation_time >= l.valid_from
+		AND (l.valid_to IS NULL OR t.observation_time < l.valid_to)
+	LEFT JOIN public.free_flow_speeds f ON t.location_id = f.location_id
+"#;
+
+/// Excludes readings of sensors that are unavailable, faulty or marked invalid,
+/// and readings that failed a plausibility check
+pub const VALID_MEASUREMENTS_FILTER: &str =
+	" AND is_valid_measurement(t.valid, t.available, t.faulty, t.plausibility_flags)";
 
 #[derive(sqlx::Type, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
 #[sqlx(type_name = "vehicle_class", rename_all = "SCREAMING_SNAKE_CASE")]
@@ -14,17 +63,41 @@ pub enum VehicleClass {
     ArticulatedTrucks,
     Unknown,
 }
+/// Quality of a speed reading, derived from the special values the MIV feed
+/// uses instead of a speed.
+#[derive(sqlx::Type, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
+#[sqlx(type_name = "data_quality", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DataQuality {
+    Valid,
+    // 252
+    NoVehicles,
+    // 251
+    SpeedUnknown,
+    // 254
+    SensorError,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FindMeasurementsParams {
 	pub lat: Option<f64>,
 	pub lon: Option<f64>,
+	// Radius in meters around lat/lon
 	pub radius: f64,
+	pub from: Option<DateTime<Utc>>,
+	pub to: Option<DateTime<Utc>>,
+	pub cursor: Option<MeasurementCursor>,
 	pub limit: i64,
+	pub include_invalid: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FindMeasurementsByLocationIdParams {
+	pub from: Option<DateTime<Utc>>,
+	pub to: Option<DateTime<Utc>>,
+	pub cursor: Option<MeasurementCursor>,
 	pub limit: i64,
+	pub include_invalid: bool,
 }
 
 impl From<i32> for VehicleClass {
@@ -40,6 +113,30 @@ impl From<i32> for VehicleClass {
     }
 }
 
+impl DataQuality {
+    pub fn from_speed(speed: i32) -> Self {
+        match speed {
+            251 => DataQuality::SpeedUnknown,
+            252 => DataQuality::NoVehicles,
+            254 => DataQuality::SensorError,
+            _ => DataQuality::Valid,
+        }
+    }
+
+    /// Combines the quality of several readings: valid as soon as one reading is
+    /// valid, otherwise the most severe reason.
+    pub fn combine(qualities: impl IntoIterator<Item = DataQuality>) -> Option<Self> {
+        qualities
+            .into_iter()
+            .max_by_key(|quality| match quality {
+                DataQuality::Valid => 3,
+                DataQuality::SensorError => 2,
+                DataQuality::SpeedUnknown => 1,
+                DataQuality::NoVehicles => 0,
+            })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TrafficMeasurement {
     pub location_id: i32,
@@ -48,10 +145,20 @@ pub struct TrafficMeasurement {
     // Calculated data
     pub occupancy_rate: i32,
     pub availability_rate: i32,
+    pub instability: i32,
 	
 	pub total_vehicles_passed: i32,
 	pub average_speed: Option<i32>,
 	pub max_speed: Option<i32>,
+	pub time_mean_speed: Option<f64>,
+	pub space_mean_speed: Option<f64>,
+	pub data_quality: Option<DataQuality>,
+
+	// Sensor status
+	pub current_publication: bool,
+	pub available: bool,
+	pub faulty: bool,
+	pub valid: bool,
 }
 
 impl TrafficMeasurement {
@@ -68,9 +175,17 @@ impl TrafficMeasurement {
 					availability_rate,
 					total_vehicles_passed,
 					average_speed,
-					max_speed
+					max_speed,
+					current_publication,
+					available,
+					faulty,
+					valid,
+					instability,
+					data_quality,
+					time_mean_speed,
+					space_mean_speed
 				)
-				VALUES ($1, $2, $3, $4, $5, $6, $7)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
 				ON CONFLICT (location_id, observation_time)
 				DO NOTHING
             "#,
@@ -80,7 +195,15 @@ impl TrafficMeasurement {
             measurement.availability_rate,
 			measurement.total_vehicles_passed,
 			measurement.average_speed,
-			measurement.max_speed
+			measurement.max_speed,
+			measurement.current_publication,
+			measurement.available,
+			measurement.faulty,
+			measurement.valid,
+			measurement.instability,
+			measurement.data_quality as Option<DataQuality>,
+			measurement.time_mean_speed,
+			measurement.space_mean_speed
         )
         .execute(pool)
         .await?;
@@ -88,15 +211,18 @@ impl TrafficMeasurement {
         Ok(())
     }
 
+	/// Inserts the measurements that are not stored yet and returns the observation
+	/// times of the rows that were actually inserted
 	pub async fn batch_insert(
 		pool: &sqlx::PgPool,
 		measurements: Vec<TrafficMeasurement>,
-	) -> Result<(), sqlx::Error> {
+	) -> Result<Vec<DateTime<Utc>>, sqlx::Error> {
 		// Split measurements into batches
 		let batches: Vec<Vec<TrafficMeasurement>> = measurements
 			.chunks(1000)
 			.map(|chunk| chunk.to_vec())
 			.collect();
+		let mut inserted = Vec::new();
     
     	// Process each batch
 		for batch in batches {
@@ -108,7 +234,15 @@ impl TrafficMeasurement {
 					availability_rate,
 					total_vehicles_passed,
 					average_speed,
-					max_speed
+					max_speed,
+					current_publication,
+					available,
+					faulty,
+					valid,
+					instability,
+					data_quality,
+					time_mean_speed,
+					space_mean_speed
 				) VALUES "
 			);
 
@@ -117,25 +251,33 @@ impl TrafficMeasurement {
 				.iter()
 				.enumerate()
 				.map(|(i, _)| {
-					let offset = i * 7;
+					let offset = i * 15;
 					format!(
-						"(${},${},${},${},${},${},${})",
+						"(${},${},${},${},${},${},${},${},${},${},${},${},${},${},${})",
 						offset + 1,
 						offset + 2,
 						offset + 3,
 						offset + 4,
 						offset + 5,
 						offset + 6,
-						offset + 7
+						offset + 7,
+						offset + 8,
+						offset + 9,
+						offset + 10,
+						offset + 11,
+						offset + 12,
+						offset + 13,
+						offset + 14,
+						offset + 15
 					)
 				})
 				.collect();
 
 			query_builder.push_str(&values.join(","));
-			query_builder.push_str(" ON CONFLICT (location_id, observation_time) DO NOTHING");
+			query_builder.push_str(" ON CONFLICT (location_id, observation_time) DO NOTHING RETURNING observation_time");
 
 			// Build the query
-			let mut query = sqlx::query(&query_builder);
+			let mut query = sqlx::query_scalar::<_, DateTime<Utc>>(&query_builder);
 
 			// Add parameters for each measurement
 			for measurement in batch {
@@ -146,77 +288,148 @@ impl TrafficMeasurement {
 					.bind(measurement.availability_rate)
 					.bind(measurement.total_vehicles_passed)
 					.bind(measurement.average_speed)
-					.bind(measurement.max_speed);
+					.bind(measurement.max_speed)
+					.bind(measurement.current_publication)
+					.bind(measurement.available)
+					.bind(measurement.faulty)
+					.bind(measurement.valid)
+					.bind(measurement.instability)
+					.bind(measurement.data_quality)
+					.bind(measurement.time_mean_speed)
+					.bind(measurement.space_mean_speed);
 			}
 
 			// Execute the batch insert
-			query.execute(pool).await?;
+			inserted.extend(query.fetch_all(pool).await?);
 		}
 
-		Ok(())
+		Ok(inserted)
 	}
 
     pub async fn get_recent(
         pool: &sqlx::PgPool,
         params: FindMeasurementsParams
 	) -> Result<Vec<MeasurementDTO>, sqlx::Error> {
-		dbg!(&params);
-        sqlx::query_as!(
-            MeasurementDTO,
-            r#"
-            SELECT
-				t.location_id,
-				t.observation_time,
-				t.occupancy_rate,
-				t.availability_rate,
-				t.total_vehicles_passed,
-				t.average_speed,
-				t.max_speed,
-				l.latitude,
-				l.longitude
-            FROM public.traffic_measurements t
-			LEFT JOIN public.locations l ON t.location_id = l.location_id
-            WHERE ST_DWithin(
-                ST_SetSRID(ST_MakePoint(l.longitude, l.latitude), 4326),
-                ST_SetSRID(ST_MakePoint($1, $2), 4326),
-                $3
-            )
-            ORDER BY observation_time DESC
-            LIMIT $4
-            "#,
-            params.lon, params.lat, params.radius, params.limit
-        )
-        .fetch_all(pool)
-        .await
+		let mut query = QueryBuilder::<Postgres>::new(SELECT_MEASUREMENTS);
+		query.push(" WHERE TRUE");
+
+		// Only filter on the radius when a center point is given
+		if let (Some(lat), Some(lon)) = (params.lat, params.lon) {
+			query
+				.push(" AND ST_DWithin(l.geog, ST_SetSRID(ST_MakePoint(")
+				.push_bind(lon)
+				.push(", ")
+				.push_bind(lat)
+				.push("), 4326)::geography, ")
+				.push_bind(params.radius)
+				.push(")");
+		}
+
+		push_time_range(&mut query, params.from, params.to);
+		push_cursor(&mut query, params.cursor);
+
+		if !params.include_invalid {
+			query.push(VALID_MEASUREMENTS_FILTER);
+		}
+
+		query
+			.push(" ORDER BY t.observation_time DESC, t.location_id DESC LIMIT ")
+			.push_bind(params.limit);
+
+		query
+			.build_query_as::<MeasurementDTO>()
+			.fetch_all(pool)
+			.await
     }
 
     pub async fn get_by_location_id(
         pool: &sqlx::PgPool,
-		location_id: String,
+		location_id: i32,
         params: FindMeasurementsByLocationIdParams
 	) -> Result<Vec<MeasurementDTO>, sqlx::Error> {
-        sqlx::query_as!(
-            MeasurementDTO,
-            r#"
-            SELECT
-				t.location_id,
-				t.observation_time,
-				t.occupancy_rate,
-				t.availability_rate,
-				t.total_vehicles_passed,
-				t.average_speed,
-				t.max_speed,
-				l.latitude,
-				l.longitude
-            FROM public.traffic_measurements t
-			LEFT JOIN public.locations l ON t.location_id = l.location_id
-            WHERE l.location_id = $1
-            ORDER BY observation_time DESC
-            LIMIT $2
-            "#,
-            location_id.parse().unwrap_or(0), params.limit
-        )
-        .fetch_all(pool)
-        .await
+		let mut query = QueryBuilder::<Postgres>::new(SELECT_MEASUREMENTS);
+		query
+			.push(" WHERE t.location_id = ")
+			.push_bind(location_id);
+
+		push_time_range(&mut query, params.from, params.to);
+		push_cursor(&mut query, params.cursor);
+
+		if !params.include_invalid {
+			query.push(VALID_MEASUREMENTS_FILTER);
+		}
+
+		query
+			.push(" ORDER BY t.observation_time DESC, t.location_id DESC LIMIT ")
+			.push_bind(params.limit);
+
+		query
+			.build_query_as::<MeasurementDTO>()
+			.fetch_all(pool)
+			.await
     }
+
+	/// Downsamples the raw measurements of a location into `bucket` wide buckets with
+	/// `time_bucket`, for bucket sizes that have no continuous aggregate
+	pub async fn get_downsampled_by_location_id(
+		pool: &sqlx::PgPool,
+		location_id: i32,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+		bucket: Duration,
+	) -> Result<Vec<AggregateDTO>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new("SELECT t.location_id, time_bucket(");
+		query
+			.push_bind(bucket)
+			.push(
+				", t.observation_time) AS bucket,
+				SUM(t.total_vehicles_passed)::BIGINT AS total_vehicles_passed,
+				SUM(t.total_vehicles_passed * t.time_mean_speed)
+					/ NULLIF(SUM(t.total_vehicles_passed) FILTER (WHERE t.time_mean_speed IS NOT NULL), 0) AS time_mean_speed,
+				SUM(t.total_vehicles_passed) FILTER (WHERE t.space_mean_speed > 0)
+					/ NULLIF(SUM(t.total_vehicles_passed / t.space_mean_speed) FILTER (WHERE t.space_mean_speed > 0), 0) AS space_mean_speed,
+				AVG(t.occupancy_rate)::DOUBLE PRECISION AS occupancy_rate,
+				COUNT(*)::DOUBLE PRECISION / "
+			)
+			.push_bind(bucket.num_minutes() as f64)
+			.push(" AS coverage, COUNT(*) AS sample_count FROM public.traffic_measurements t WHERE t.location_id = ")
+			.push_bind(location_id);
+
+		push_time_range(&mut query, Some(from), Some(to));
+		query
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(" GROUP BY t.location_id, 2 ORDER BY 2");
+
+		query
+			.build_query_as::<AggregateDTO>()
+			.fetch_all(pool)
+			.await
+	}
+}
+
+/// Continues after the measurement the cursor points at
+fn push_cursor(query: &mut QueryBuilder<Postgres>, cursor: Option<MeasurementCursor>) {
+	if let Some(cursor) = cursor {
+		query
+			.push(" AND (t.observation_time, t.location_id) < (")
+			.push_bind(cursor.observation_time)
+			.push(", ")
+			.push_bind(cursor.location_id)
+			.push(")");
+	}
+}
+
+/// Restricts the observation time to [from, to)
+fn push_time_range(query: &mut QueryBuilder<Postgres>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) {
+	if let Some(from) = from {
+		query
+			.push(" AND t.observation_time >= ")
+			.push_bind(from);
+	}
+
+	if let Some(to) = to {
+		query
+			.push(" AND t.observation_time < ")
+			.push_bind(to);
+	}
 }
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use super::traffic_measurement::{DataQuality, VehicleClass, VALID_MEASUREMENTS_FILTER};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct TrafficMeasurementClass {
+	pub location_id: i32,
+	pub observation_time: DateTime<Utc>,
+	pub vehicle_class: VehicleClass,
+
+	pub traffic_intensity: i32,
+	pub vehicle_speed_arithmetic: Option<i32>,
+	pub vehicle_speed_harmonic: Option<i32>,
+	pub data_quality: DataQuality,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FindMeasurementClassesParams {
+	pub from: Option<DateTime<Utc>>,
+	pub to: Option<DateTime<Utc>>,
+	// All classes when omitted
+	pub vehicle_classes: Option<Vec<VehicleClass>>,
+	pub limit: i64,
+}
+
+/// Vehicle class names as the API accepts and returns them
+pub const VEHICLE_CLASS_NAMES: [&str; 6] = [
+	"MOTOR_BIKES",
+	"CARS",
+	"VANS",
+	"RIGID_TRUCKS",
+	"ARTICULATED_TRUCKS",
+	"UNKNOWN",
+];
+
+/// Parses a comma separated list of vehicle class names, e.g. both truck classes for a
+/// truck share. `None` when a name is unknown or the list is empty.
+pub fn parse_vehicle_classes(value: &str) -> Option<Vec<VehicleClass>> {
+	let mut vehicle_classes = Vec::new();
+
+	for name in value.split(',') {
+		let vehicle_class = match name.trim() {
+			"MOTOR_BIKES" => VehicleClass::MotorBikes,
+			"CARS" => VehicleClass::Cars,
+			"VANS" => VehicleClass::Vans,
+			"RIGID_TRUCKS" => VehicleClass::RigidTrucks,
+			"ARTICULATED_TRUCKS" => VehicleClass::ArticulatedTrucks,
+			"UNKNOWN" => VehicleClass::Unknown,
+			_ => return None,
+		};
+
+		if !vehicle_classes.contains(&vehicle_class) {
+			vehicle_classes.push(vehicle_class);
+		}
+	}
+
+	Some(vehicle_classes)
+}
+
+impl TrafficMeasurementClass {
+	pub async fn batch_insert(
+		pool: &sqlx::PgPool,
+		measurements: Vec<TrafficMeasurementClass>,
+	) -> Result<(), sqlx::Error> {
+		// Split measurements into batches
+		let batches: Vec<Vec<TrafficMeasurementClass>> = measurements
+			.chunks(1000)
+			.map(|chunk| chunk.to_vec())
+			.collect();
+
+		// Process each batch
+		for batch in batches {
+			let mut query_builder = String::from(
+				"INSERT INTO public.traffic_measurement_classes (
+					location_id,
+					observation_time,
+					vehicle_class,
+					traffic_intensity,
+					vehicle_speed_arithmetic,
+					vehicle_speed_harmonic,
+					data_quality
+				) VALUES "
+			);
+
+			// Build the values part of the query and collect params
+			let values: Vec<String> = batch
+				.iter()
+				.enumerate()
+				.map(|(i, _)| {
+					let offset = i * 7;
+					format!(
+						"(${},${},${},${},${},${},${})",
+						offset + 1,
+						offset + 2,
+						offset + 3,
+						offset + 4,
+						offset + 5,
+						offset + 6,
+						offset + 7
+					)
+				})
+				.collect();
+
+			query_builder.push_str(&values.join(","));
+			query_builder.push_str(" ON CONFLICT (location_id, observation_time, vehicle_class) DO NOTHING");
+
+			// Build the query
+			let mut query = sqlx::query(&query_builder);
+
+			// Add parameters for each measurement
+			for measurement in batch {
+				query = query
+					.bind(measurement.location_id)
+					.bind(measurement.observation_time)
+					.bind(measurement.vehicle_class)
+					.bind(measurement.traffic_intensity)
+					.bind(measurement.vehicle_speed_arithmetic)
+					.bind(measurement.vehicle_speed_harmonic)
+					.bind(measurement.data_quality);
+			}
+
+			// Execute the batch insert
+			query.execute(pool).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Per-class readings of a location, newest first. Only classes of a valid
+	/// measurement are returned, since the class rows carry no sensor status.
+	pub async fn get_by_location_id(
+		pool: &sqlx::PgPool,
+		location_id: i32,
+		params: FindMeasurementClassesParams
+	) -> Result<Vec<TrafficMeasurementClass>, sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(
+			"SELECT
+				c.location_id,
+				c.observation_time,
+				c.vehicle_class,
+				c.traffic_intensity,
+				c.vehicle_speed_arithmetic,
+				c.vehicle_speed_harmonic,
+				c.data_quality
+			FROM public.traffic_measurement_classes c
+			JOIN public.traffic_measurements t ON t.location_id = c.location_id
+				AND t.observation_time = c.observation_time
+			WHERE c.location_id = "
+		);
+		query.push_bind(location_id);
+
+		if let Some(from) = params.from {
+			query
+				.push(" AND c.observation_time >= ")
+				.push_bind(from);
+		}
+
+		if let Some(to) = params.to {
+			query
+				.push(" AND c.observation_time < ")
+				.push_bind(to);
+		}
+
+		if let Some(vehicle_classes) = params.vehicle_classes {
+			query
+				.push(" AND c.vehicle_class = ANY(")
+				.push_bind(vehicle_classes)
+				.push(")");
+		}
+
+		query
+			.push(VALID_MEASUREMENTS_FILTER)
+			.push(" ORDER BY c.observation_time DESC, c.vehicle_class LIMIT ")
+			.push_bind(params.limit);
+
+		query
+			.build_query_as::<TrafficMeasurementClass>()
+			.fetch_all(pool)
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_a_list_of_vehicle_classes() {
+		assert_eq!(
+			parse_vehicle_classes("RIGID_TRUCKS, ARTICULATED_TRUCKS"),
+			Some(vec![VehicleClass::RigidTrucks, VehicleClass::ArticulatedTrucks]),
+		);
+		assert_eq!(parse_vehicle_classes("CARS,CARS"), Some(vec![VehicleClass::Cars]));
+	}
+
+	#[test]
+	fn accepts_the_names_the_api_returns() {
+		let vehicle_classes = parse_vehicle_classes(&VEHICLE_CLASS_NAMES.join(",")).unwrap();
+
+		assert_eq!(vehicle_classes.len(), VEHICLE_CLASS_NAMES.len());
+		for (vehicle_class, name) in vehicle_classes.iter().zip(VEHICLE_CLASS_NAMES) {
+			assert_eq!(serde_json::to_value(vehicle_class).unwrap(), name);
+		}
+	}
+
+	#[test]
+	fn rejects_unknown_or_missing_names() {
+		for malformed in ["", "TRUCKS", "cars", "CARS,", ",CARS"] {
+			assert_eq!(parse_vehicle_classes(malformed), None, "{}", malformed);
+		}
+	}
+}
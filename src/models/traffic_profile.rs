@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+
+use crate::dto::profile::{TrafficProfileRefreshDTO, TrafficProfileSlotDTO};
+
+/// Minimum number of one-minute readings in a 15 minute bucket for it to count towards
+/// a profile, partially covered buckets would drag the intensity down
+const MIN_BUCKET_SAMPLES: i64 = 10;
+
+pub struct TrafficProfile;
+
+impl TrafficProfile {
+	/// Whether Postgres knows `time_zone`, so it can be used with AT TIME ZONE
+	pub async fn is_known_time_zone(
+		pool: &sqlx::PgPool,
+		time_zone: &str,
+	) -> Result<bool, sqlx::Error> {
+		sqlx::query_scalar!(
+			r#"SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1) as "known!""#,
+			time_zone
+		)
+		.fetch_one(pool)
+		.await
+	}
+
+	/// Rebuilds the profiles of all locations from the 15 minute aggregates between
+	/// `window_start` and `window_end`. Weekdays and slots are taken in `time_zone`.
+	pub async fn refresh(
+		pool: &sqlx::PgPool,
+		window_start: DateTime<Utc>,
+		window_end: DateTime<Utc>,
+		time_zone: &str,
+	) -> Result<TrafficProfileRefreshDTO, sqlx::Error> {
+		let mut tx = pool.begin().await?;
+
+		sqlx::query!("DELETE FROM public.traffic_profiles")
+			.execute(&mut *tx)
+			.await?;
+
+		let profile_count = sqlx::query!(
+			r#"
+			INSERT INTO public.traffic_profiles (
+				location_id,
+				weekday,
+				slot_start,
+				sample_count,
+				average_intensity,
+				intensity_p10,
+				intensity_p50,
+				intensity_p90,
+				average_speed,
+				speed_p10,
+				speed_p50,
+				speed_p90,
+				average_occupancy,
+				occupancy_p10,
+				occupancy_p50,
+				occupancy_p90
+			)
+			SELECT
+				location_id,
+				weekday,
+				slot_start,
+				COUNT(*),
+				AVG(intensity),
+				percentile_cont(0.1) WITHIN GROUP (ORDER BY intensity),
+				percentile_cont(0.5) WITHIN GROUP (ORDER BY intensity),
+				percentile_cont(0.9) WITHIN GROUP (ORDER BY intensity),
+				AVG(speed),
+				percentile_cont(0.1) WITHIN GROUP (ORDER BY speed),
+				percentile_cont(0.5) WITHIN GROUP (ORDER BY speed),
+				percentile_cont(0.9) WITHIN GROUP (ORDER BY speed),
+				AVG(occupancy),
+				percentile_cont(0.1) WITHIN GROUP (ORDER BY occupancy),
+				percentile_cont(0.5) WITHIN GROUP (ORDER BY occupancy),
+				percentile_cont(0.9) WITHIN GROUP (ORDER BY occupancy)
+			FROM (
+				SELECT
+					location_id,
+					EXTRACT(ISODOW FROM bucket AT TIME ZONE $3)::SMALLINT AS weekday,
+					(bucket AT TIME ZONE $3)::TIME AS slot_start,
+					total_vehicles_passed::DOUBLE PRECISION AS intensity,
+					time_mean_speed_sum / NULLIF(time_mean_vehicles, 0) AS speed,
+					occupancy_rate_sum::DOUBLE PRECISION / NULLIF(occupancy_rate_count, 0) AS occupancy
+				FROM public.traffic_measurements_15m
+				WHERE bucket >= $1 AND bucket < $2 AND sample_count >= $4
+			) buckets
+			GROUP BY location_id, weekday, slot_start
+			"#,
+			window_start, window_end, time_zone, MIN_BUCKET_SAMPLES
+		)
+		.execute(&mut *tx)
+		.await?
+		.rows_affected();
+
+		let refresh = sqlx::query_as!(
+			TrafficProfileRefreshDTO,
+			r#"
+			INSERT INTO public.traffic_profile_refreshes (
+				window_start,
+				window_end,
+				time_zone,
+				profile_count
+			)
+			VALUES ($1, $2, $3, $4)
+			RETURNING refreshed_at, window_start, window_end, time_zone, profile_count
+			"#,
+			window_start, window_end, time_zone, profile_count as i32
+		)
+		.fetch_one(&mut *tx)
+		.await?;
+
+		tx.commit().await?;
+
+		Ok(refresh)
+	}
+
+	pub async fn get_latest_refresh(
+		pool: &sqlx::PgPool,
+	) -> Result<Option<TrafficProfileRefreshDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			TrafficProfileRefreshDTO,
+			r#"
+			SELECT
+				refreshed_at,
+				window_start,
+				window_end,
+				time_zone,
+				profile_count
+			FROM public.traffic_profile_refreshes
+			ORDER BY refreshed_at DESC
+			LIMIT 1
+			"#
+		)
+		.fetch_optional(pool)
+		.await
+	}
+
+	pub async fn get_by_location_id(
+		pool: &sqlx::PgPool,
+		location_id: i32,
+		weekday: Option<i16>,
+	) -> Result<Vec<TrafficProfileSlotDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			TrafficProfileSlotDTO,
+			r#"
+			SELECT
+				weekday,
+				slot_start,
+				sample_count,
+				average_intensity,
+				intensity_p10,
+				intensity_p50,
+				intensity_p90,
+				average_speed,
+				speed_p10,
+				speed_p50,
+				speed_p90,
+				average_occupancy,
+				occupancy_p10,
+				occupancy_p50,
+				occupancy_p90
+			FROM public.traffic_profiles
+			WHERE location_id = $1 AND ($2::SMALLINT IS NULL OR weekday = $2)
+			ORDER BY weekday, slot_start
+			"#,
+			location_id, weekday
+		)
+		.fetch_all(pool)
+		.await
+	}
+}
@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+
+use crate::dto::travel_time::{RouteTravelTimePointDTO, SegmentTravelTimeDTO};
+
+use super::site::RoadDirection;
+
+// Speeds below this are treated as this speed, a standing queue still moves eventually
+const MIN_SPEED: f64 = 5.0;
+// Segment readings older than this do not count as the current travel time
+const MAX_SEGMENT_AGE_MINUTES: i64 = 15;
+// Sites further apart than this are too far to interpolate between, the stretch
+// stays uncovered instead
+const MAX_SEGMENT_LENGTH_KM: f64 = 5.0;
+// Rounding margin when checking that a route is fully covered, in km
+const COVERAGE_TOLERANCE_KM: f64 = 0.001;
+
+pub struct SegmentTravelTime;
+
+impl SegmentTravelTime {
+	/// Computes the travel time of every segment between consecutive main carriageway
+	/// sites for the minutes observed since `since`. Each half of a segment is driven at
+	/// the space-mean speed of the site at that end; sites without vehicles use their
+	/// free-flow speed and a site without any reading takes the speed of the other end.
+	/// Segments longer than `MAX_SEGMENT_LENGTH_KM` are left out, so routes across them
+	/// are reported as incomplete rather than guessed.
+	/// Returns the number of stored segment travel times.
+	pub async fn compute_since(
+		pool: &sqlx::PgPool,
+		since: DateTime<Utc>,
+	) -> Result<u64, sqlx::Error> {
+		let result = sqlx::query!(
+			r#"
+			WITH site_speeds AS (
+				SELECT
+					s.site_id,
+					t.observation_time,
+					COALESCE(
+						SUM(t.total_vehicles_passed) FILTER (WHERE t.space_mean_speed > 0)
+							/ NULLIF(SUM(t.total_vehicles_passed / t.space_mean_speed) FILTER (WHERE t.space_mean_speed > 0), 0),
+						AVG(f.free_flow_speed)
+					) AS speed
+				FROM public.measuring_sites s
+				INNER JOIN public.traffic_measurements t ON t.location_id = ANY(s.location_ids)
+				LEFT JOIN public.free_flow_speeds f ON f.location_id = t.location_id
+				WHERE s.main_carriageway
+					AND s.road IS NOT NULL
+					AND s.kilometer_point IS NOT NULL
+					AND t.observation_time >= $1
+					AND is_valid_measurement(t.valid, t.available, t.faulty, t.plausibility_flags)
+				GROUP BY s.site_id, t.observation_time
+			),
+			segments AS (
+				SELECT
+					site_id AS from_site_id,
+					LEAD(site_id) OVER corridor AS to_site_id,
+					road,
+					direction,
+					kilometer_point AS from_kilometer_point,
+					LEAD(kilometer_point) OVER corridor AS to_kilometer_point
+				FROM public.measuring_sites
+				WHERE main_carriageway
+					AND road IS NOT NULL
+					AND kilometer_point IS NOT NULL
+				WINDOW corridor AS (
+					PARTITION BY road, direction
+					ORDER BY CASE WHEN direction = 'POSITIVE' THEN kilometer_point ELSE -kilometer_point END
+				)
+			),
+			segment_speeds AS (
+				SELECT
+					g.*,
+					ABS(g.to_kilometer_point - g.from_kilometer_point) AS length_km,
+					m.observation_time,
+					GREATEST(COALESCE(a.speed, b.speed), $2) AS from_speed,
+					GREATEST(COALESCE(b.speed, a.speed), $2) AS to_speed
+				FROM segments g
+				-- Every minute in which either end reported
+				CROSS JOIN LATERAL (
+					SELECT observation_time FROM site_speeds WHERE site_id = g.from_site_id
+					UNION
+					SELECT observation_time FROM site_speeds WHERE site_id = g.to_site_id
+				) m
+				LEFT JOIN site_speeds a ON a.site_id = g.from_site_id AND a.observation_time = m.observation_time
+				LEFT JOIN site_speeds b ON b.site_id = g.to_site_id AND b.observation_time = m.observation_time
+				WHERE g.to_site_id IS NOT NULL
+					AND ABS(g.to_kilometer_point - g.from_kilometer_point) <= $3
+			)
+			INSERT INTO public.segment_travel_times (
+				from_site_id,
+				to_site_id,
+				observation_time,
+				road,
+				direction,
+				from_kilometer_point,
+				to_kilometer_point,
+				length_km,
+				from_speed,
+				to_speed,
+				travel_time_seconds
+			)
+			SELECT
+				from_site_id,
+				to_site_id,
+				observation_time,
+				road,
+				direction,
+				from_kilometer_point,
+				to_kilometer_point,
+				length_km,
+				from_speed,
+				to_speed,
+				length_km / 2 * (1 / from_speed + 1 / to_speed) * 3600
+			FROM segment_speeds
+			WHERE from_speed IS NOT NULL AND to_speed IS NOT NULL
+			ON CONFLICT (from_site_id, to_site_id, observation_time) DO UPDATE SET
+				from_speed = EXCLUDED.from_speed,
+				to_speed = EXCLUDED.to_speed,
+				travel_time_seconds = EXCLUDED.travel_time_seconds
+			"#,
+			since, MIN_SPEED, MAX_SEGMENT_LENGTH_KM
+		)
+		.execute(pool)
+		.await?;
+
+		Ok(result.rows_affected())
+	}
+
+	/// Latest travel time, at or before `at`, of the segments that overlap the route
+	/// between `from_km` and `to_km`, in driving order
+	pub async fn get_route_segments(
+		pool: &sqlx::PgPool,
+		road: &str,
+		direction: RoadDirection,
+		from_km: f64,
+		to_km: f64,
+		at: DateTime<Utc>,
+	) -> Result<Vec<SegmentTravelTimeDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			SegmentTravelTimeDTO,
+			r#"
+			SELECT
+				from_site_id,
+				to_site_id,
+				observation_time,
+				from_kilometer_point,
+				to_kilometer_point,
+				length_km,
+				from_speed,
+				to_speed,
+				travel_time_seconds,
+				route_km as "route_km!"
+			FROM (
+				SELECT DISTINCT ON (from_site_id, to_site_id)
+					*,
+					LEAST(GREATEST(from_kilometer_point, to_kilometer_point), GREATEST($3::DOUBLE PRECISION, $4::DOUBLE PRECISION))
+						- GREATEST(LEAST(from_kilometer_point, to_kilometer_point), LEAST($3::DOUBLE PRECISION, $4::DOUBLE PRECISION)) AS route_km
+				FROM public.segment_travel_times
+				WHERE road = $1 AND direction = $2
+					AND observation_time <= $5
+					AND observation_time > $5 - make_interval(mins => $6)
+				ORDER BY from_site_id, to_site_id, observation_time DESC
+			) latest
+			WHERE route_km > 0 AND length_km > 0
+			ORDER BY CASE WHEN direction = 'POSITIVE' THEN from_kilometer_point ELSE -from_kilometer_point END
+			"#,
+			road, direction as RoadDirection, from_km, to_km, at, MAX_SEGMENT_AGE_MINUTES as i32
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	/// Route travel time per observed minute between `from` and `to`, summing the
+	/// part of every segment that lies on the route. Minutes in which the segments do
+	/// not cover the whole route have no travel time.
+	pub async fn get_route_history(
+		pool: &sqlx::PgPool,
+		road: &str,
+		direction: RoadDirection,
+		from_km: f64,
+		to_km: f64,
+		from: DateTime<Utc>,
+		to: DateTime<Utc>,
+	) -> Result<Vec<RouteTravelTimePointDTO>, sqlx::Error> {
+		sqlx::query_as!(
+			RouteTravelTimePointDTO,
+			r#"
+			SELECT
+				observation_time,
+				CASE WHEN SUM(route_km) >= ABS($4::DOUBLE PRECISION - $3::DOUBLE PRECISION) - $7::DOUBLE PRECISION
+					THEN SUM(travel_time_seconds * route_km / length_km)
+				END as travel_time_seconds,
+				SUM(route_km) as "covered_km!",
+				SUM(route_km) >= ABS($4::DOUBLE PRECISION - $3::DOUBLE PRECISION) - $7::DOUBLE PRECISION as "complete!"
+			FROM (
+				SELECT
+					*,
+					LEAST(GREATEST(from_kilometer_point, to_kilometer_point), GREATEST($3::DOUBLE PRECISION, $4::DOUBLE PRECISION))
+						- GREATEST(LEAST(from_kilometer_point, to_kilometer_point), LEAST($3::DOUBLE PRECISION, $4::DOUBLE PRECISION)) AS route_km
+				FROM public.segment_travel_times
+				WHERE road = $1 AND direction = $2
+					AND observation_time >= $5
+					AND observation_time < $6
+			) segments
+			WHERE route_km > 0 AND length_km > 0
+			GROUP BY observation_time
+			ORDER BY observation_time
+			"#,
+			road, direction as RoadDirection, from_km, to_km, from, to, COVERAGE_TOLERANCE_KM
+		)
+		.fetch_all(pool)
+		.await
+	}
+
+	pub fn is_complete(covered_km: f64, from_km: f64, to_km: f64) -> bool {
+		covered_km >= (to_km - from_km).abs() - COVERAGE_TOLERANCE_KM
+	}
+}
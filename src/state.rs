@@ -3,4 +3,6 @@ use sqlx::{Pool, Postgres};
 #[derive(Clone, Debug)]
 pub struct AppState {
 	pub pool: Pool<Postgres>,
+	// PROFILE_TIME_ZONE, validated at startup
+	pub time_zone: String,
 }
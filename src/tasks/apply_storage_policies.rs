@@ -0,0 +1,132 @@
+use std::env;
+
+use crate::{errors::AppError, models::storage_policy::StoragePolicy};
+
+use super::config::invalid_configuration;
+
+// Defaults in days, matching the policies created by the migrations
+const DEFAULT_RAW_COMPRESS_AFTER_DAYS: i32 = 7;
+const DEFAULT_RAW_RETENTION_DAYS: i32 = 90;
+const DEFAULT_AGGREGATE_15M_RETENTION_DAYS: i32 = 365;
+const DEFAULT_AGGREGATE_HOURLY_RETENTION_DAYS: i32 = 3 * 365;
+
+// Raw data has to outlive the window the 15 minute aggregate refreshes from it by this much,
+// so a refresh never runs over chunks that are about to be dropped
+const RAW_RETENTION_MARGIN_DAYS: i32 = 1;
+
+/// Reads a number of days from the environment. `0` or `off` disables the policy.
+fn days_from_env(name: &str, default: i32) -> Result<Option<i32>, AppError> {
+	let Ok(value) = env::var(name) else {
+		return Ok(Some(default));
+	};
+
+	match value.trim() {
+		"0" | "off" => Ok(None),
+		value => value
+			.parse::<i32>()
+			.ok()
+			.filter(|days| *days > 0)
+			.map(Some)
+			.ok_or_else(|| invalid_configuration(format!("{} must be a positive number of days, 0 or off", name))),
+	}
+}
+
+/// Applies the compression and retention policies from the environment:
+///
+/// - `RAW_COMPRESS_AFTER_DAYS`: compress raw measurements older than this (default 7)
+/// - `RAW_RETENTION_DAYS`: drop raw measurements older than this (default 90)
+/// - `AGGREGATE_15M_RETENTION_DAYS`: drop 15 minute aggregates older than this (default 365)
+/// - `AGGREGATE_HOURLY_RETENTION_DAYS`: drop hourly aggregates older than this (default 1095)
+/// - `AGGREGATE_DAILY_RETENTION_DAYS`: drop daily aggregates older than this (default off)
+///
+/// Aggregates are never dropped before the raw data they are built from.
+pub async fn apply_storage_policies(pool: &sqlx::PgPool) -> Result<(), AppError> {
+	let raw_compress_after = days_from_env("RAW_COMPRESS_AFTER_DAYS", DEFAULT_RAW_COMPRESS_AFTER_DAYS)?;
+	let raw_retention = days_from_env("RAW_RETENTION_DAYS", DEFAULT_RAW_RETENTION_DAYS)?;
+	let aggregate_15m_retention = days_from_env("AGGREGATE_15M_RETENTION_DAYS", DEFAULT_AGGREGATE_15M_RETENTION_DAYS)?;
+	let aggregate_hourly_retention = days_from_env("AGGREGATE_HOURLY_RETENTION_DAYS", DEFAULT_AGGREGATE_HOURLY_RETENTION_DAYS)?;
+	let aggregate_daily_retention = match env::var("AGGREGATE_DAILY_RETENTION_DAYS") {
+		Ok(_) => days_from_env("AGGREGATE_DAILY_RETENTION_DAYS", 0)?,
+		Err(_) => None,
+	};
+
+	// Only the 15 minute aggregate reads raw data, the coarser ones are built from aggregates
+	let min_raw_retention = StoragePolicy::refresh_start_offset_days(pool, "traffic_measurements_15m")
+		.await?
+		.map(|days| days.ceil() as i32 + RAW_RETENTION_MARGIN_DAYS);
+
+	if let (Some(raw_retention), Some(min_raw_retention)) = (raw_retention, min_raw_retention) {
+		if raw_retention < min_raw_retention {
+			return Err(invalid_configuration(format!("RAW_RETENTION_DAYS must be at least {}", min_raw_retention)));
+		}
+	}
+
+	// Each aggregate level has to be kept at least as long as the level it is built from
+	let retentions = [
+		("RAW_RETENTION_DAYS", raw_retention),
+		("AGGREGATE_15M_RETENTION_DAYS", aggregate_15m_retention),
+		("AGGREGATE_HOURLY_RETENTION_DAYS", aggregate_hourly_retention),
+		("AGGREGATE_DAILY_RETENTION_DAYS", aggregate_daily_retention),
+	];
+
+	for pair in retentions.windows(2) {
+		let [(finer_name, finer), (coarser_name, coarser)] = pair else {
+			continue;
+		};
+
+		let shorter = match (finer, coarser) {
+			(None, Some(_)) => true,
+			(Some(finer), Some(coarser)) => coarser < finer,
+			_ => false,
+		};
+
+		if shorter {
+			return Err(invalid_configuration(format!("{} can not be shorter than {}", coarser_name, finer_name)));
+		}
+	}
+
+	let policies = [
+		StoragePolicy {
+			relation: "public.traffic_measurements".to_owned(),
+			compressed: true,
+			compress_after_days: raw_compress_after,
+			drop_after_days: raw_retention,
+		},
+		StoragePolicy {
+			relation: "public.traffic_measurement_classes".to_owned(),
+			compressed: true,
+			compress_after_days: raw_compress_after,
+			drop_after_days: raw_retention,
+		},
+		StoragePolicy {
+			relation: "public.segment_travel_times".to_owned(),
+			compressed: false,
+			compress_after_days: None,
+			drop_after_days: raw_retention,
+		},
+		StoragePolicy {
+			relation: "public.traffic_measurements_15m".to_owned(),
+			compressed: false,
+			compress_after_days: None,
+			drop_after_days: aggregate_15m_retention,
+		},
+		StoragePolicy {
+			relation: "public.traffic_measurements_hourly".to_owned(),
+			compressed: false,
+			compress_after_days: None,
+			drop_after_days: aggregate_hourly_retention,
+		},
+		StoragePolicy {
+			relation: "public.traffic_measurements_daily".to_owned(),
+			compressed: false,
+			compress_after_days: None,
+			drop_after_days: aggregate_daily_retention,
+		},
+	];
+
+	for policy in policies {
+		policy.apply(pool).await?;
+	}
+
+	Ok(())
+}
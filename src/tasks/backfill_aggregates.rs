@@ -0,0 +1,13 @@
+use crate::{errors::AppError, models::traffic_aggregate::TrafficAggregate};
+
+/// Materializes the continuous aggregates over all raw measurements that are still kept.
+/// They are created without data and their refresh policies only look back a few days,
+/// so without this the history from before they were created would never show up.
+pub async fn backfill_aggregates(pool: &sqlx::PgPool) -> Result<(), AppError> {
+	TrafficAggregate::refresh_all(pool)
+		.await?;
+
+	println!("Backfilled the traffic measurement aggregates");
+
+	Ok(())
+}
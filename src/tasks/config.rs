@@ -0,0 +1,28 @@
+use std::{env, str::FromStr};
+
+use actix_web::http::StatusCode;
+
+use crate::errors::{AppError, AppErrorValue};
+
+pub fn invalid_configuration(message: String) -> AppError {
+	AppError::InternalServerError(AppErrorValue {
+		message,
+		status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+		code: "INVALID_CONFIGURATION".to_owned(),
+		..Default::default()
+	})
+}
+
+/// Reads a positive number from the environment, `default` when it is not set.
+/// Anything else is refused rather than silently replaced by the default.
+pub fn positive_from_env<T: FromStr + PartialOrd + Default>(name: &str, default: T) -> Result<T, AppError> {
+	let Ok(value) = env::var(name) else {
+		return Ok(default);
+	};
+
+	value.trim()
+		.parse::<T>()
+		.ok()
+		.filter(|value| *value > T::default())
+		.ok_or_else(|| invalid_configuration(format!("{} must be a positive number", name)))
+}
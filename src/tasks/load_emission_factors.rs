@@ -0,0 +1,74 @@
+use std::{env, fs};
+
+use crate::{errors::AppError, models::{emission::EmissionFactor, traffic_measurement::VehicleClass}};
+
+use super::config::invalid_configuration;
+
+const DEFAULT_EMISSION_FACTORS_PATH: &str = "emission_factors.json";
+
+const VEHICLE_CLASSES: [VehicleClass; 6] = [
+	VehicleClass::MotorBikes,
+	VehicleClass::Cars,
+	VehicleClass::Vans,
+	VehicleClass::RigidTrucks,
+	VehicleClass::ArticulatedTrucks,
+	VehicleClass::Unknown,
+];
+
+/// Checks that the speed bands of every vehicle class start at 0 km/h, connect
+/// without gaps or overlaps and end in an open ended band
+fn validate_factors(factors: &[EmissionFactor]) -> Result<(), AppError> {
+	for factor in factors {
+		if [factor.min_speed, factor.co2, factor.nox, factor.pm].iter().any(|value| value.is_nan() || *value < 0.0) {
+			return Err(invalid_configuration(format!("Emission factors of {:?} can not be negative", factor.vehicle_class)));
+		}
+	}
+
+	for vehicle_class in VEHICLE_CLASSES {
+		let mut bands: Vec<&EmissionFactor> = factors
+			.iter()
+			.filter(|factor| factor.vehicle_class == vehicle_class)
+			.collect();
+		bands.sort_by(|a, b| a.min_speed.total_cmp(&b.min_speed));
+
+		let mut expected_min_speed = Some(0.0);
+
+		for band in bands {
+			if expected_min_speed != Some(band.min_speed) {
+				return Err(invalid_configuration(format!("Speed bands of {:?} must connect, starting at 0 km/h", vehicle_class)));
+			}
+
+			if band.max_speed.is_some_and(|max_speed| max_speed <= band.min_speed) {
+				return Err(invalid_configuration(format!("Speed band of {:?} at {} km/h is empty", vehicle_class, band.min_speed)));
+			}
+
+			expected_min_speed = band.max_speed;
+		}
+
+		if expected_min_speed.is_some() {
+			return Err(invalid_configuration(format!("Speed bands of {:?} must end in an open ended band", vehicle_class)));
+		}
+	}
+
+	Ok(())
+}
+
+/// Loads the emission factor table from the JSON file at `EMISSION_FACTORS_PATH`
+/// (default `emission_factors.json`) and stores it for the emission estimates
+pub async fn load_emission_factors(pool: &sqlx::PgPool) -> Result<(), AppError> {
+	let path = env::var("EMISSION_FACTORS_PATH").unwrap_or_else(|_| DEFAULT_EMISSION_FACTORS_PATH.to_owned());
+
+	let contents = fs::read_to_string(&path)
+		.map_err(|err| invalid_configuration(format!("Reading emission factors from {} failed: {}", path, err)))?;
+	let factors: Vec<EmissionFactor> = serde_json::from_str(&contents)
+		.map_err(|err| invalid_configuration(format!("Parsing emission factors from {} failed: {}", path, err)))?;
+
+	validate_factors(&factors)?;
+
+	EmissionFactor::replace_all(pool, &factors)
+		.await?;
+
+	println!("Loaded {} emission factors from {}", factors.len(), path);
+
+	Ok(())
+}
@@ -1 +1,8 @@
 pub mod seed_traffic_data;
+pub mod apply_storage_policies;
+pub mod refresh_traffic_profiles;
+pub mod refresh_free_flow_speeds;
+pub mod refresh_road_kpis;
+pub mod load_emission_factors;
+pub mod config;
+pub mod backfill_aggregates;
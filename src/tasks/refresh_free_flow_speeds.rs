@@ -0,0 +1,39 @@
+use std::env;
+
+use chrono::{Duration, DurationRound, Utc};
+use sqlx::postgres::PgPoolOptions;
+
+use crate::{errors::AppError, models::congestion::FreeFlowSpeed};
+
+use super::{config::positive_from_env, refresh_traffic_profiles::profile_time_zone};
+
+const DEFAULT_FREE_FLOW_WINDOW_DAYS: i64 = 28;
+
+/// Number of full days the baselines are learned from, `FREE_FLOW_WINDOW_DAYS` (default 4 weeks)
+pub fn free_flow_window_days() -> Result<i64, AppError> {
+	positive_from_env("FREE_FLOW_WINDOW_DAYS", DEFAULT_FREE_FLOW_WINDOW_DAYS)
+}
+
+/// Relearns the free-flow speed baselines from the night-time data of the last
+/// `FREE_FLOW_WINDOW_DAYS` full days (default 4 weeks)
+pub async fn refresh_free_flow_speeds() -> std::result::Result<(), AppError> {
+	let pool = PgPoolOptions::new()
+		.max_connections(5)
+		.connect(&env::var("DATABASE_URL").expect("DATABASE_URL missing"))
+		.await?;
+
+	let window_days = free_flow_window_days()?;
+	let time_zone = profile_time_zone(&pool).await?;
+
+	let window_end = Utc::now()
+		.duration_trunc(Duration::days(1))
+		.expect("day truncation");
+	let window_start = window_end - Duration::days(window_days);
+
+	let updated = FreeFlowSpeed::refresh(&pool, window_start, window_end, &time_zone)
+		.await?;
+
+	println!("Refreshed free-flow speeds of {} locations", updated);
+
+	Ok(())
+}
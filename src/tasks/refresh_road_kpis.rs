@@ -0,0 +1,41 @@
+use std::env;
+
+use chrono::Duration;
+use sqlx::postgres::PgPoolOptions;
+
+use crate::{errors::AppError, models::road_kpi::RoadKpi};
+
+use super::{config::positive_from_env, refresh_traffic_profiles::profile_time_zone};
+
+const DEFAULT_ROAD_KPI_DAYS: i64 = 3;
+
+/// Number of past full days that are recomputed, `ROAD_KPI_DAYS` (default 3)
+pub fn road_kpi_days() -> Result<i64, AppError> {
+	positive_from_env("ROAD_KPI_DAYS", DEFAULT_ROAD_KPI_DAYS)
+}
+
+/// Recomputes the daily road KPIs of the last `ROAD_KPI_DAYS` full days (default 3),
+/// so late data and aggregate refreshes still end up in the reports
+pub async fn refresh_road_kpis() -> std::result::Result<(), AppError> {
+	let pool = PgPoolOptions::new()
+		.max_connections(5)
+		.connect(&env::var("DATABASE_URL").expect("DATABASE_URL missing"))
+		.await?;
+
+	let days = road_kpi_days()?;
+
+	let time_zone = profile_time_zone(&pool).await?;
+	// The KPIs are bucketed by local day, so only local days before today are complete
+	let today = RoadKpi::local_today(&pool, &time_zone)
+		.await?;
+
+	for offset in 1..=days {
+		let day = today - Duration::days(offset);
+		let updated = RoadKpi::refresh_day(&pool, day, &time_zone)
+			.await?;
+
+		println!("Refreshed road KPIs of {} for {} roads", day, updated);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,53 @@
+use std::env;
+
+use chrono::{Duration, DurationRound, Utc};
+use sqlx::postgres::PgPoolOptions;
+
+use crate::{errors::AppError, models::traffic_profile::TrafficProfile};
+
+use super::config::{invalid_configuration, positive_from_env};
+
+const DEFAULT_PROFILE_WINDOW_DAYS: i64 = 56;
+const DEFAULT_PROFILE_TIME_ZONE: &str = "Europe/Brussels";
+
+/// Time zone used for weekdays and times of day, `PROFILE_TIME_ZONE` (default Europe/Brussels)
+pub async fn profile_time_zone(pool: &sqlx::PgPool) -> Result<String, AppError> {
+	let time_zone = env::var("PROFILE_TIME_ZONE")
+		.unwrap_or_else(|_| DEFAULT_PROFILE_TIME_ZONE.to_owned());
+
+	if !TrafficProfile::is_known_time_zone(pool, &time_zone).await? {
+		return Err(invalid_configuration(format!("PROFILE_TIME_ZONE '{}' is not a known time zone", time_zone)));
+	}
+
+	Ok(time_zone)
+}
+
+/// Number of full days the profiles are built from, `PROFILE_WINDOW_DAYS` (default 8 weeks)
+pub fn profile_window_days() -> Result<i64, AppError> {
+	positive_from_env("PROFILE_WINDOW_DAYS", DEFAULT_PROFILE_WINDOW_DAYS)
+}
+
+/// Rebuilds the typical-day profiles over the last `PROFILE_WINDOW_DAYS` full days
+/// (default 8 weeks), using weekdays and slots in `PROFILE_TIME_ZONE`.
+pub async fn refresh_traffic_profiles() -> std::result::Result<(), AppError> {
+	let pool = PgPoolOptions::new()
+		.max_connections(5)
+		.connect(&env::var("DATABASE_URL").expect("DATABASE_URL missing"))
+		.await?;
+
+	let window_days = profile_window_days()?;
+	let time_zone = profile_time_zone(&pool).await?;
+
+	// Whole UTC days, so the window does not depend on when the job runs
+	let window_end = Utc::now()
+		.duration_trunc(Duration::days(1))
+		.expect("day truncation");
+	let window_start = window_end - Duration::days(window_days);
+
+	let refresh = TrafficProfile::refresh(&pool, window_start, window_end, &time_zone)
+		.await?;
+
+	println!("Refreshed {} traffic profile slots", refresh.profile_count);
+
+	Ok(())
+}
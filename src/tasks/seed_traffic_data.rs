@@ -1,16 +1,163 @@
 
-use std::env;
+use std::{env, str::FromStr};
 
+use chrono::{DateTime, Duration, Utc};
 use quick_xml::de::from_str;
 use sqlx::postgres::PgPoolOptions;
 
-use crate::{errors::AppError, models::{location::Location, traffic_measurement::TrafficMeasurement}, TrafficData, TrafficDataLocations};
+use crate::{errors::AppError, MeasurementData, MeasuringPoint, models::{current_traffic_measurement::CurrentTrafficMeasurement, location::Location, location_version::LocationVersion, plausibility::{Plausibility, PlausibilityRules}, traffic_measurement::{DataQuality, TrafficMeasurement}, traffic_measurement_class::TrafficMeasurementClass, traffic_anomaly::TrafficAnomaly, travel_time::SegmentTravelTime, queue::QueueEvent, sensor_health::{EquipmentOutage, SensorHealth}}, TrafficData, TrafficDataLocations};
+
+use super::{config::{invalid_configuration, positive_from_env}, refresh_traffic_profiles::profile_time_zone};
+
+const DEFAULT_ANOMALY_MIN_SEVERITY: f64 = 3.0;
+
+// The stages after the insert only look at the readings inserted by the run, and at most
+// this far before the newest of them, so a late station does not make every run rescan history
+const MAX_INGESTION_LOOKBACK_MINUTES: i64 = 5;
+
+const DEFAULT_PLAUSIBILITY_STUCK_MINUTES: i32 = 30;
+const DEFAULT_PLAUSIBILITY_DUPLICATE_MIN_VEHICLES: i32 = 5;
+const DEFAULT_PLAUSIBILITY_MAX_SPEED: i32 = 200;
+const DEFAULT_PLAUSIBILITY_OCCUPANCY_TOLERANCE: f64 = 3.0;
+
+/// Deviation from the profile, in standard deviations, from which a reading is an
+/// anomaly. Configured with `ANOMALY_MIN_SEVERITY`.
+pub fn anomaly_min_severity() -> Result<f64, AppError> {
+	positive_from_env("ANOMALY_MIN_SEVERITY", DEFAULT_ANOMALY_MIN_SEVERITY)
+}
+
+/// Reads a positive plausibility threshold from the environment, `off` disables the check
+fn plausibility_rule<T: FromStr + PartialOrd + Default>(name: &str, default: T) -> Result<Option<T>, AppError> {
+	match env::var(name) {
+		Ok(value) if value.trim() == "off" => Ok(None),
+		_ => positive_from_env(name, default)
+			.map(Some)
+			.map_err(|_| invalid_configuration(format!("{} must be a positive number or off", name))),
+	}
+}
+
+/// Plausibility rules, configured with `PLAUSIBILITY_STUCK_MINUTES`,
+/// `PLAUSIBILITY_DUPLICATE_MIN_VEHICLES`, `PLAUSIBILITY_MAX_SPEED` and
+/// `PLAUSIBILITY_OCCUPANCY_TOLERANCE`
+pub fn plausibility_rules() -> Result<PlausibilityRules, AppError> {
+	let occupancy_tolerance = plausibility_rule("PLAUSIBILITY_OCCUPANCY_TOLERANCE", DEFAULT_PLAUSIBILITY_OCCUPANCY_TOLERANCE)?;
+
+	// Below 1 the allowed band turns inside out and every reading with a speed would be flagged
+	if occupancy_tolerance.is_some_and(|tolerance| tolerance < 1.0) {
+		return Err(invalid_configuration("PLAUSIBILITY_OCCUPANCY_TOLERANCE must be at least 1 or off".to_owned()));
+	}
+
+	Ok(PlausibilityRules {
+		stuck_minutes: plausibility_rule("PLAUSIBILITY_STUCK_MINUTES", DEFAULT_PLAUSIBILITY_STUCK_MINUTES)?,
+		duplicate_min_vehicles: plausibility_rule("PLAUSIBILITY_DUPLICATE_MIN_VEHICLES", DEFAULT_PLAUSIBILITY_DUPLICATE_MIN_VEHICLES)?,
+		max_speed: plausibility_rule("PLAUSIBILITY_MAX_SPEED", DEFAULT_PLAUSIBILITY_MAX_SPEED)?,
+		occupancy_tolerance,
+	})
+}
+
+/// Start of the window the stages after the insert process: the oldest observation time
+/// inserted by the run, bounded by `MAX_INGESTION_LOOKBACK_MINUTES` before the newest.
+/// `None` when the run inserted nothing new.
+fn ingestion_since(inserted_observation_times: &[DateTime<Utc>]) -> Option<DateTime<Utc>> {
+	let oldest = inserted_observation_times.iter().min()?;
+	let newest = inserted_observation_times.iter().max()?;
+
+	Some((*oldest).max(*newest - Duration::minutes(MAX_INGESTION_LOOKBACK_MINUTES)))
+}
+
+/// Intensity-weighted mean of the per class arithmetic speeds (time-mean speed)
+fn time_mean_speed(measurement_data: &[MeasurementData]) -> Option<f64> {
+	let (vehicles, weighted_speed) = measurement_data.iter()
+		.filter(|m| m.traffic_intensity > 0 && DataQuality::from_speed(m.vehicle_speed_arithmetic) == DataQuality::Valid)
+		.fold((0.0, 0.0), |(vehicles, weighted_speed), m| {
+			(
+				vehicles + m.traffic_intensity as f64,
+				weighted_speed + m.traffic_intensity as f64 * m.vehicle_speed_arithmetic as f64,
+			)
+		});
+
+	(vehicles > 0.0).then(|| weighted_speed / vehicles)
+}
+
+/// Harmonic mean of the per class harmonic speeds, weighted by intensity (space-mean speed)
+fn space_mean_speed(measurement_data: &[MeasurementData]) -> Option<f64> {
+	let (vehicles, inverse_speed) = measurement_data.iter()
+		.filter(|m| {
+			m.traffic_intensity > 0
+				&& m.vehicle_speed_harmonic > 0
+				&& DataQuality::from_speed(m.vehicle_speed_harmonic) == DataQuality::Valid
+		})
+		.fold((0.0, 0.0), |(vehicles, inverse_speed), m| {
+			(
+				vehicles + m.traffic_intensity as f64,
+				inverse_speed + m.traffic_intensity as f64 / m.vehicle_speed_harmonic as f64,
+			)
+		});
+
+	(vehicles > 0.0).then(|| vehicles / inverse_speed)
+}
+
+/// The reading of a measuring point, combined over its vehicle classes
+fn traffic_measurement(point: &MeasuringPoint) -> TrafficMeasurement {
+	let valid_speeds: Vec<i32> = point.measurement_data.iter()
+		.map(|m| m.vehicle_speed_arithmetic)
+		.filter(|&speed| DataQuality::from_speed(speed) == DataQuality::Valid)
+		.collect();
+
+	let data_quality = DataQuality::combine(
+		point.measurement_data.iter()
+			.map(|m| DataQuality::from_speed(m.vehicle_speed_arithmetic))
+	);
+
+	let total_vehicles_passed = point.measurement_data.iter()
+		.map(|m| m.traffic_intensity)
+		.collect::<Vec<i32>>()
+		.into_iter()
+		.sum::<i32>();
+
+	let average_speed = if !valid_speeds.is_empty() {
+		Some((valid_speeds.iter().sum::<i32>() as f64 / valid_speeds.len() as f64).round() as i32)
+	} else {
+		None
+	};
+
+	let max_speed = if !valid_speeds.is_empty() {
+		Some(*valid_speeds.iter().max().unwrap_or(&0))
+	} else {
+		None
+	};
+
+	let time_mean_speed = time_mean_speed(&point.measurement_data);
+	let space_mean_speed = space_mean_speed(&point.measurement_data);
+
+	TrafficMeasurement {
+		location_id: point.unique_id,
+		observation_time: point.observation_time.into(),
+		occupancy_rate: point.calculated_data.occupancy_rate,
+		availability_rate: point.calculated_data.availability_rate,
+		instability: point.calculated_data.instability,
+		total_vehicles_passed,
+		average_speed,
+		max_speed,
+		time_mean_speed,
+		space_mean_speed,
+		data_quality,
+		current_publication: point.current_publication == 1,
+		available: point.available == 1,
+		faulty: point.faulty == 1,
+		// The feed marks valid readings with geldig 0, unlike beschikbaar and defect
+		valid: point.valid == 0,
+	}
+}
 
 pub async fn seed_traffic_data() -> std::result::Result<(), AppError> {
     let pool = PgPoolOptions::new()
         .max_connections(5)
         .connect(&env::var("DATABASE_URL").expect("DATABASE_URL missing"))
         .await?;
+	let min_severity = anomaly_min_severity()?;
+	let rules = plausibility_rules()?;
+	let time_zone = profile_time_zone(&pool).await?;
 
 	let traffic_data_xml = reqwest::get("http://miv.opendata.belfla.be/miv/verkeersdata")
         .await?
@@ -29,58 +176,246 @@ pub async fn seed_traffic_data() -> std::result::Result<(), AppError> {
 		.into_iter()
 		.map(|location| {
 			Location {
+				location_id: location.unique_id,
+				descriptive_id: location.descriptive_id,
+				full_name: location.full_name,
+				ident_8: location.ident_8,
+				equipment_number: location.equipment_number,
+				kilometer_point: location.kilometer_point,
+				lane: location.lane,
+				lambert_x: location.lambert_x,
+				lambert_y: location.lambert_y,
 				latitude: location.latitude,
 				longitude: location.longitude,
-				location_id: location.unique_id
 			}
 		})
 		.collect::<Vec<Location>>();
-	dbg!(&locations_to_insert.len());
-	Location::batch_insert(&pool, locations_to_insert)
+	// The configuration is republished every minute, the locations only change with a new one
+	let configuration_changed = LocationVersion::sync(&pool, location_data.publication_time.into(), &locations_to_insert)
 		.await?;
+	if configuration_changed {
+		println!("Imported a new configuration of {} locations", locations_to_insert.len());
+		Location::batch_insert(&pool, locations_to_insert)
+			.await?;
+	}
 	
-	const SPECIAL_VALUES: &[i32] = &[251, 252, 254];
-	let traffic_measurements_to_insert = traffic_data.measuring_points
-		.into_iter()
-		.map(|point| {
-			let valid_speeds: Vec<i32> = point.measurement_data.iter()
-				.map(|m| m.vehicle_speed_arithmetic)
-				.filter(|&speed| !SPECIAL_VALUES.contains(&speed))
-				.collect();
-
-			let total_vehicles_passed = point.measurement_data.iter()
-				.map(|m| m.traffic_intensity)
-				.collect::<Vec<i32>>()
-				.into_iter()
-				.sum::<i32>();
-
-			let average_speed = if !valid_speeds.is_empty() {
-				Some((valid_speeds.iter().sum::<i32>() as f64 / valid_speeds.len() as f64).round() as i32)
-			} else {
-				None
-			};
-
-			let max_speed = if !valid_speeds.is_empty() {
-				Some(valid_speeds.iter().max().unwrap_or(&0).clone())
-			} else {
-				None
-			};
-
-			TrafficMeasurement {
-				location_id: point.unique_id,
-				observation_time: point.observation_time.into(),
-				occupancy_rate: point.calculated_data.occupancy_rate,
-				availability_rate: point.calculated_data.availability_rate,
-				total_vehicles_passed,
-				average_speed,
-				max_speed
-				
-			}
+	let traffic_measurement_classes_to_insert = traffic_data.measuring_points
+		.iter()
+		.flat_map(|point| {
+			point.measurement_data.iter().map(|m| {
+				let data_quality = DataQuality::from_speed(m.vehicle_speed_arithmetic);
+				let is_valid = data_quality == DataQuality::Valid;
+
+				TrafficMeasurementClass {
+					location_id: point.unique_id,
+					observation_time: point.observation_time.into(),
+					vehicle_class: m.vehicle_class,
+					traffic_intensity: m.traffic_intensity,
+					vehicle_speed_arithmetic: is_valid.then_some(m.vehicle_speed_arithmetic),
+					vehicle_speed_harmonic: (is_valid && DataQuality::from_speed(m.vehicle_speed_harmonic) == DataQuality::Valid)
+						.then_some(m.vehicle_speed_harmonic),
+					data_quality,
+				}
+			})
 		})
+		.collect::<Vec<TrafficMeasurementClass>>();
+
+	let traffic_measurements_to_insert = traffic_data.measuring_points
+		.iter()
+		.map(traffic_measurement)
 		.collect::<Vec<TrafficMeasurement>>();
-	dbg!(&traffic_measurements_to_insert.len());
-	TrafficMeasurement::batch_insert(&pool, traffic_measurements_to_insert)
+	// Readings a station keeps republishing are already stored and do not widen the window
+	let measurement_count = traffic_measurements_to_insert.len();
+	let inserted_observation_times = TrafficMeasurement::batch_insert(&pool, traffic_measurements_to_insert)
+		.await?;
+	println!("Stored {} new of {} published measurements", inserted_observation_times.len(), measurement_count);
+	// Stored before the derived stages, the feed only publishes the current minute
+	TrafficMeasurementClass::batch_insert(&pool, traffic_measurement_classes_to_insert)
+		.await?;
+
+	if let Some(since) = ingestion_since(&inserted_observation_times) {
+		// Flag implausible readings first so the later stages can leave them out
+		Plausibility::check_since(&pool, since, &rules)
+			.await?;
+
+		CurrentTrafficMeasurement::refresh_since(&pool, since)
+			.await?;
+
+		TrafficAnomaly::detect_since(&pool, since, &time_zone, min_severity)
+			.await?;
+
+		SegmentTravelTime::compute_since(&pool, since)
+			.await?;
+
+		QueueEvent::detect(&pool)
+			.await?;
+
+		SensorHealth::update_since(&pool, since)
+			.await?;
+	}
+
+	// Runs without new readings still have to notice stations going silent
+	EquipmentOutage::detect(&pool)
 		.await?;
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::models::traffic_measurement::VehicleClass;
+
+	use super::*;
+
+	fn class(traffic_intensity: i32, vehicle_speed_arithmetic: i32, vehicle_speed_harmonic: i32) -> MeasurementData {
+		MeasurementData {
+			vehicle_class: VehicleClass::Cars,
+			traffic_intensity,
+			vehicle_speed_arithmetic,
+			vehicle_speed_harmonic,
+		}
+	}
+
+	// A measuring point in the layout of miv.opendata.belfla.be/miv/verkeersdata, normal readings carry geldig 0
+	const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<miv>
+    <tijd_publicatie>2024-12-02T10:15:41.404+01:00</tijd_publicatie>
+    <tijd_laatste_config_wijziging>2019-12-05T10:26:49+01:00</tijd_laatste_config_wijziging>
+    <meetpunt beschrijvende_id="H292L20" unieke_id="3638">
+        <lve_nr>437</lve_nr>
+        <tijd_waarneming>2024-12-02T10:14:00+01:00</tijd_waarneming>
+        <tijd_laatst_gewijzigd>2024-12-02T10:15:21.087+01:00</tijd_laatst_gewijzigd>
+        <actueel_publicatie>1</actueel_publicatie>
+        <beschikbaar>1</beschikbaar>
+        <defect>0</defect>
+        <geldig>0</geldig>
+        <meetdata klasse_id="1">
+            <verkeersintensiteit>0</verkeersintensiteit>
+            <voertuigsnelheid_rekenkundig>252</voertuigsnelheid_rekenkundig>
+            <voertuigsnelheid_harmonisch>252</voertuigsnelheid_harmonisch>
+        </meetdata>
+        <meetdata klasse_id="2">
+            <verkeersintensiteit>14</verkeersintensiteit>
+            <voertuigsnelheid_rekenkundig>112</voertuigsnelheid_rekenkundig>
+            <voertuigsnelheid_harmonisch>110</voertuigsnelheid_harmonisch>
+        </meetdata>
+        <meetdata klasse_id="3">
+            <verkeersintensiteit>2</verkeersintensiteit>
+            <voertuigsnelheid_rekenkundig>98</voertuigsnelheid_rekenkundig>
+            <voertuigsnelheid_harmonisch>97</voertuigsnelheid_harmonisch>
+        </meetdata>
+        <meetdata klasse_id="4">
+            <verkeersintensiteit>0</verkeersintensiteit>
+            <voertuigsnelheid_rekenkundig>252</voertuigsnelheid_rekenkundig>
+            <voertuigsnelheid_harmonisch>252</voertuigsnelheid_harmonisch>
+        </meetdata>
+        <meetdata klasse_id="5">
+            <verkeersintensiteit>1</verkeersintensiteit>
+            <voertuigsnelheid_rekenkundig>86</voertuigsnelheid_rekenkundig>
+            <voertuigsnelheid_harmonisch>86</voertuigsnelheid_harmonisch>
+        </meetdata>
+        <rekendata>
+            <bezettingsgraad>4</bezettingsgraad>
+            <beschikbaarheidsgraad>100</beschikbaarheidsgraad>
+            <onrustigheid>0</onrustigheid>
+        </rekendata>
+    </meetpunt>
+</miv>"#;
+
+	#[test]
+	fn reads_a_published_measuring_point() {
+		let traffic_data = from_str::<TrafficData>(SAMPLE).unwrap();
+		let point = &traffic_data.measuring_points[0];
+
+		assert_eq!(point.unique_id, 3638);
+		assert_eq!(point.measurement_data.len(), 5);
+		assert_eq!((point.available, point.faulty, point.valid), (1, 0, 0));
+
+		let measurement = traffic_measurement(point);
+
+		assert!(measurement.available);
+		assert!(!measurement.faulty);
+		assert!(measurement.valid);
+		assert!(measurement.current_publication);
+		assert_eq!(measurement.total_vehicles_passed, 17);
+		assert_eq!(measurement.data_quality, Some(DataQuality::Valid));
+		assert_eq!(measurement.max_speed, Some(112));
+	}
+
+	#[test]
+	fn geldig_1_marks_the_reading_invalid() {
+		let traffic_data = from_str::<TrafficData>(&SAMPLE.replace("<geldig>0</geldig>", "<geldig>1</geldig>")).unwrap();
+
+		assert!(!traffic_measurement(&traffic_data.measuring_points[0]).valid);
+	}
+
+	#[test]
+	fn ingestion_since_starts_at_the_oldest_inserted_reading() {
+		let newest = DateTime::from_timestamp(1_733_130_000, 0).unwrap();
+
+		assert_eq!(ingestion_since(&[newest, newest - Duration::minutes(2)]), Some(newest - Duration::minutes(2)));
+		assert_eq!(ingestion_since(&[]), None);
+	}
+
+	#[test]
+	fn ingestion_since_bounds_late_readings_by_the_lookback() {
+		let newest = DateTime::from_timestamp(1_733_130_000, 0).unwrap();
+
+		assert_eq!(
+			ingestion_since(&[newest, newest - Duration::minutes(50)]),
+			Some(newest - Duration::minutes(MAX_INGESTION_LOOKBACK_MINUTES)),
+		);
+	}
+
+	#[test]
+	fn time_mean_speed_weights_by_intensity() {
+		let speed = time_mean_speed(&[class(10, 100, 100), class(30, 80, 80)]).unwrap();
+
+		assert!((speed - 85.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn time_mean_speed_skips_special_values_and_empty_classes() {
+		let measurement_data = [
+			class(10, 100, 100),
+			class(5, 251, 251),
+			class(5, 252, 252),
+			class(5, 254, 254),
+			class(0, 50, 50),
+		];
+
+		assert_eq!(time_mean_speed(&measurement_data), Some(100.0));
+	}
+
+	#[test]
+	fn time_mean_speed_is_none_without_vehicles() {
+		assert_eq!(time_mean_speed(&[class(0, 252, 252), class(3, 254, 254)]), None);
+		assert_eq!(time_mean_speed(&[]), None);
+	}
+
+	#[test]
+	fn space_mean_speed_is_the_weighted_harmonic_mean() {
+		let speed = space_mean_speed(&[class(10, 100, 100), class(30, 80, 80)]).unwrap();
+
+		assert!((speed - 40.0 / (10.0 / 100.0 + 30.0 / 80.0)).abs() < 1e-9);
+	}
+
+	#[test]
+	fn space_mean_speed_skips_special_values_zero_speeds_and_empty_classes() {
+		let measurement_data = [
+			class(10, 90, 90),
+			class(5, 251, 251),
+			class(5, 252, 252),
+			class(5, 254, 254),
+			class(5, 60, 0),
+			class(0, 50, 50),
+		];
+
+		assert_eq!(space_mean_speed(&measurement_data), Some(90.0));
+	}
+
+	#[test]
+	fn space_mean_speed_is_none_without_vehicles() {
+		assert_eq!(space_mean_speed(&[class(0, 100, 100), class(4, 251, 251)]), None);
+	}
+}